@@ -1,8 +1,8 @@
-use std::collections::HashMap;
 use std::io::{stdin, prelude::*};
 use std::str::FromStr;
 
-use painting::{Point,Dir,Len,PointSet};
+use painting::{Point,Dir,Len,Segment};
+use painting::geom::{Crossing, crossings};
 
 fn parse_one(token: &str) -> (Dir, Len) {
     let c0 = token.chars().next().expect("empty move");
@@ -15,41 +15,33 @@ fn parse(line: &str) -> Vec<(Dir, Len)> {
     line.split(',').map(parse_one).collect()
 }
 
-fn all_points(r: &[(Dir, Len)]) -> impl Iterator<Item = Point> + '_ {
-    Point::origin().walk_many(r.iter().cloned())
+fn segments(r: &[(Dir, Len)]) -> Vec<Segment> {
+    Segment::path(Point::origin(), r.iter().cloned())
 }
 
-fn all_crossings<'a, 'b>(r0: &'a[(Dir, Len)], r1: &'b[(Dir, Len)])
-                         -> impl Iterator<Item = (usize, Point)> + 'b
-{
-    let mut ps = PointSet::new();
-    for p in all_points(r0) {
-        ps.insert(p);
-    }
-    all_points(r1)
-        .enumerate()
-        .map(|(i, p)| (i+1, p)) // Sigh
-        .filter(move |&(_i, p)| ps.contains(p))
+// In the order the second wire reaches them.
+fn all_crossings(r0: &[(Dir, Len)], r1: &[(Dir, Len)]) -> Vec<Crossing> {
+    let mut isects = crossings(&segments(r0), &segments(r1));
+    isects.sort_by_key(|c| c.steps.1);
+    isects
 }
 
 fn part1(s: &str, q: &str) -> Len {
     all_crossings(&parse(s), &parse(q))
-        .map(|(_i, p)| (p - Point::origin()).len())
+        .iter()
+        .map(|c| (c.at - Point::origin()).len())
         .min()
         .unwrap()
 }
 
 fn part2(s: &str, q: &str) -> Len {
-    let sp = parse(s);
-    let qp = parse(q);
-    let mut qls = HashMap::new();
-    for (ql, pt) in all_crossings(&sp, &qp) {
-        qls.entry(pt).or_insert(ql); // want the lowest if multiple
-    }
-    all_crossings(&qp, &sp)
-        .map(|(sl, pt)| sl + *(qls.get(&pt).expect("asymmetrical crossings!?")))
+    // Every pair of visits to a crossing is listed, so the minimum sum
+    // uses each wire's first visit.
+    all_crossings(&parse(s), &parse(q))
+        .iter()
+        .map(|c| c.steps.0 + c.steps.1)
         .min()
-        .unwrap() as Len
+        .unwrap()
 }
 
 fn main() {
@@ -78,7 +70,8 @@ mod test {
         let isects: Vec<_> =
             all_crossings(&parse("R8,U5,L5,D3"),
                           &parse("U7,R6,D4,L4"))
-            .map(|(_i, pt)| pt)
+            .iter()
+            .map(|c| c.at)
             .collect();
         assert_eq!(isects, vec![Point { x: 6, y: 5 }, Point { x: 3, y: 3 }]);
     }
@@ -1,70 +1,15 @@
-use std::mem::drop;
-use std::{thread, sync::mpsc};
-
-use intcode::{Computer, Device, IOError, Word};
-
-struct ParDev {
-    recv: mpsc::Receiver<Word>,
-    send: mpsc::Sender<Word>,
-    phase: Option<Word>,
-    last: Option<Option<Word>>,
-}
-
-impl Device for ParDev {
-    fn input(&mut self) -> Result<Word, IOError> {
-        if let Some(phase) = self.phase.take() {
-            return Ok(phase + 5);
-        }
-        Ok(self.recv.recv().expect("recv error"))
-    }
-    fn output(&mut self, val: Word) -> Result<(), IOError> {
-        let send_result = self.send.send(val);
-        Ok(if let Some(ref mut last) = self.last {
-            *last = Some(val);
-        } else {
-            send_result.expect("send error");
-        })
-    }
-}
+use intcode::{Computer, Word};
+use intcode::net::{Framing, Network, Status, Topology};
 
 pub fn par_amp(cpu: &Computer, phases: &[Word]) -> Word {
-    let mut sends = vec![];
-    let mut recvs = vec![];
-
-    let n = phases.len();
-    for _i in 0..n {
-        let (s, r) = mpsc::channel::<Word>();
-        sends.push(Some(s));
-        recvs.push(Some(r));
-    }
-    let mut joins = vec![];
-    let (final_s, final_r) = mpsc::channel::<Word>();
-    let init_s = sends[0].clone().unwrap();
-    for i in 0..n {
-        let recv = recvs[i].take().unwrap();
-        let send = sends[(i + 1) % n].take().unwrap();
-        let cpu = cpu.clone();
-        let final_s = if i == n - 1 { Some(final_s.clone()) } else { None };
-        let phase = Some(phases[i]);
-        joins.push(thread::spawn(move || {
-            let mut cpu = cpu;
-            let mut dev = ParDev {
-                recv, send, phase, last: final_s.as_ref().map(|_| None)
-            };
-            cpu.run(&mut dev).expect("magic smoke escaped");
-            if let Some(final_s) = final_s {
-                final_s.send(dev.last.unwrap().expect("no output")).expect("final send error");
-            }
-        }));
-    }
-    drop(final_s);
-    init_s.send(0).expect("initial send error");
-    drop(init_s);
-    let last = final_r.recv().expect("final recv error");
-    for join in joins {
-        join.join().expect("join error");
-    }
-    return last;
+    let mut net = Network::new(cpu, Topology::Ring(phases.len()), Framing::Stream);
+    for (i, &phase) in phases.iter().enumerate() {
+        net.inject(i, &[phase + 5]);
+    }
+    net.inject(0, &[0]);
+    assert_eq!(net.run_threaded().expect("magic smoke escaped"), Status::Halted);
+    // The last amp's final output is left in the first amp's queue.
+    *net.queue(0).back().expect("no output")
 }
 
 #[cfg(test)]
@@ -1,5 +1,7 @@
 use std::io::{stdin, prelude::*};
-use std::str;
+
+use painting::{Grid, ocr};
+use painting::render::{View, YAxis};
 
 const WIDTH: usize = 25;
 const HEIGHT: usize = 6;
@@ -34,9 +36,13 @@ fn undercomp(below: &[u8], above: &mut[u8]) {
     }
 }
 
-fn display(slice: &[u8]) {
-    for row in slices(slice, WIDTH) {
-        println!("{}", str::from_utf8(row).unwrap());
+fn display(image: Vec<u8>) {
+    let grid = Grid::from_vec(WIDTH, image);
+    let view = View::new(grid.bounding_box(), YAxis::Down);
+    print!("{}", view.to_text(|p| grid[p] as char));
+    match ocr::recognize(&view, |p| grid[p] == b'1') {
+        Ok(text) => println!("{}", text),
+        Err(err) => eprintln!("can't read that: {}", err),
     }
 }
 
@@ -53,7 +59,7 @@ fn main() {
     let data = stdin.lock().lines().next().expect("no input").expect("I/O error reading stdin");
     let data = data.into_bytes();
     println!("{}", part1(&data));
-    display(&part2(&data));
+    display(part2(&data));
 }
 
 #[cfg(test)]
@@ -4,6 +4,8 @@ use std::io::{stdin, prelude::*};
 use std::iter::FromIterator;
 use std::mem;
 
+use painting::Grid;
+
 type Num = i32;
 
 // FIXME may not work on negative numbers (but I probably don't need to care).
@@ -138,17 +140,12 @@ impl FromIterator<String> for Map {
     fn from_iter<I>(iter: I) -> Self
         where I: IntoIterator<Item = String>
     {
-        let mut this = Map(HashSet::new());
-        for (y, line) in iter.into_iter().enumerate() {
-            for (x, ch) in line.chars().enumerate() {
-                match ch {
-                    '#' => assert!(this.0.insert(Point::new(x as Num, y as Num))),
-                    '.' => (),
-                    _ => panic!("bad map char {:?}", ch)
-                }
-            }
-        }
-        this
+        let grid = Grid::from_ascii(iter, |ch| match ch {
+            '#' => true,
+            '.' => false,
+            _ => panic!("bad map char {:?}", ch)
+        }).expect("ragged map");
+        Map(grid.iter().filter(|&(_xy, &ast)| ast).map(|(xy, _)| Point::new(xy.x, xy.y)).collect())
     }
 }
 
@@ -1,14 +1,14 @@
 use std::io::{stdin, prelude::*};
 
 use intcode::{Computer, Device, IOError, Word};
-use painting::{PointSet, Point, Move, Dir};
+use painting::{PointSet, Point, Dir, Turtle, ocr};
+use painting::render::{View, YAxis};
 
 #[derive(Debug, Clone)]
 struct PaintDev {
     canvas: PointSet,
     mask: PointSet,
-    turtle: Point,
-    heading: Move,
+    turtle: Turtle,
     ldisc: LDisc
 }
 
@@ -23,8 +23,7 @@ impl PaintDev {
         Self {
             canvas: PointSet::new(),
             mask: PointSet::new(),
-            turtle: Point::origin(),
-            heading: Dir::Up.to_move(),
+            turtle: Turtle::new(Point::origin(), Dir::Up),
             ldisc: LDisc::Paint,
         }
     }
@@ -38,40 +37,40 @@ impl PaintDev {
     }
 
     fn print(&self) {
-        let (low, high) = self.canvas.bounding_box();
-        for y in (low.y..=high.y).rev() {
-            let mut line = String::new();
-            for x in low.x..high.x {
-                line.push(if self.canvas.contains(Point { x, y }) { '#' } else { '.' })
-            }
-            println!("{}", line);
+        let view = View::new(self.canvas.bounding_box(), YAxis::Up);
+        print!("{}", view.to_text(|p| if self.canvas.contains(p) { '#' } else { '.' }));
+        match ocr::recognize_set(&self.canvas, YAxis::Up) {
+            Ok(text) => println!("{}", text),
+            Err(err) => eprintln!("can't read that: {}", err),
         }
     }
 }
 
 impl Device for PaintDev {
     fn input(&mut self) -> Result<Word, IOError> {
-        Ok(if self.canvas.contains(self.turtle) { 1 } else { 0 })
+        Ok(if self.canvas.contains(self.turtle.pos) { 1 } else { 0 })
     }
 
     fn output(&mut self, val: Word) -> Result<(), IOError> {
         Ok(self.ldisc = match self.ldisc {
             LDisc::Paint => {
                 match val {
-                    0 => self.canvas.remove(self.turtle),
-                    1 => self.canvas.insert(self.turtle),
+                    0 => self.canvas.remove(self.turtle.pos),
+                    1 => self.canvas.insert(self.turtle.pos),
                     _ => return Err(IOError)
                 };
-                self.mask.insert(self.turtle);
+                self.mask.insert(self.turtle.pos);
                 LDisc::Turn
             }
             LDisc::Turn => {
-                self.heading = match val {
-                    0 => self.heading.turn_left(),
-                    1 => self.heading.turn_right(),
+                match val {
+                    0 => self.turtle.left(),
+                    1 => self.turtle.right(),
                     _ => return Err(IOError)
                 };
-                self.turtle += self.heading;
+                self.turtle.forward(1);
+                // Only the position matters, so don't let the path grow.
+                self.turtle.clear_path();
                 LDisc::Paint
             }
         })
@@ -117,20 +116,20 @@ mod test {
         assert!(dev.canvas.contains(Point::origin()));
         assert_eq!(dev.mask.len(), 1);
         assert!(dev.mask.contains(Point::origin()));
-        assert_eq!(dev.turtle, Point{ x: -1, y: 0 });
-        assert_eq!(dev.heading, Dir::Lf.to_move());
+        assert_eq!(dev.turtle.pos, Point{ x: -1, y: 0 });
+        assert_eq!(dev.turtle.heading, Dir::Lf);
 
         supply(&mut dev, &[0,0]);
         assert_eq!(dev.canvas.len(), 1);
         assert_eq!(dev.mask.len(), 2);
-        assert_eq!(dev.turtle, Point{ x: -1, y: -1 });
+        assert_eq!(dev.turtle.pos, Point{ x: -1, y: -1 });
 
         supply(&mut dev, &[1,0, 1,0]);
         assert_eq!(dev.input(), Ok(1));
         assert_eq!(dev.canvas.len(), 3);
         assert_eq!(dev.mask.len(), 4);
-        assert_eq!(dev.turtle, Point::origin());
-        assert_eq!(dev.heading, Dir::Up.to_move());
+        assert_eq!(dev.turtle.pos, Point::origin());
+        assert_eq!(dev.turtle.heading, Dir::Up);
 
         supply(&mut dev, &[0,1, 1,0, 1,0]);
         assert_eq!(dev.canvas.len(), 4);
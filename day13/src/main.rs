@@ -1,10 +1,11 @@
-use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::env::args;
 use std::fs::OpenOptions;
 use std::io::{stdin, prelude::*, BufReader, BufWriter};
 use std::ops::Drop;
 
 use intcode::{Computer, Device, Word, IOError};
+use painting::{Coord, Point, PointMap};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Tile {
@@ -68,14 +69,14 @@ impl CmdBuf {
 
 #[derive(Debug, Clone)]
 struct ScreenDev {
-    tiles: HashMap<(Word, Word), Tile>,
+    tiles: PointMap<Tile>,
     cmd: CmdBuf
 }
 
 impl ScreenDev {
     fn new() -> Self {
         Self {
-            tiles: HashMap::new(),
+            tiles: PointMap::new(),
             cmd: CmdBuf::new(),
         }
     }
@@ -86,7 +87,11 @@ impl Device for ScreenDev {
 
     fn output(&mut self, val: Word) -> Result<(), IOError> {
         Ok(match self.cmd.handle(val)? {
-            Cmd::Draw(x, y, tile) => { self.tiles.insert((x, y), tile); }
+            Cmd::Draw(x, y, tile) => {
+                let pos = Point { x: Coord::try_from(x).map_err(|_| IOError)?,
+                                  y: Coord::try_from(y).map_err(|_| IOError)? };
+                self.tiles.insert(pos, tile);
+            }
             Cmd::None => (),
             _ => return Err(IOError),
         })
@@ -240,7 +245,7 @@ fn main() {
     if cmd == "blocks" {
         let mut dev = ScreenDev::new();
         cpu.run(&mut dev).expect("runtime error");
-        println!("{}", dev.tiles.iter().filter(|&(_xy, &t)| t == Tile::Block).count());
+        println!("{}", dev.tiles.values().filter(|&&t| t == Tile::Block).count());
     } else if cmd == "play" {
         let tty_in = OpenOptions::new().read(true).open("/dev/tty")
                                                   .expect("error opening /dev/tty for read");
@@ -1,7 +1,9 @@
 use std::io::{stdin, prelude::*};
 
 use intcode::{Computer, Device, IOError, Word, ExecError, exec::ExecFault};
-use painting::{PointSet, Point, Dir};
+use painting::{PointMap, Point, Dir};
+use painting::render::{View, YAxis};
+use painting::search::flood_fill;
 
 const DIRS: &[Dir] = &[Dir::Up, Dir::Dn, Dir::Lf, Dir::Rt];
 
@@ -14,9 +16,14 @@ fn cmd_of_dir(d: Dir) -> Word {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Cell {
+    Wall,
+    Open,
+}
+
 struct SearchDev {
-    walls: PointSet,
-    open: PointSet,
+    map: PointMap<Cell>,
     here: Point,
     trail: Vec<Dir>,
     backtrack: Option<Dir>,
@@ -27,11 +34,10 @@ struct SearchDev {
 impl SearchDev {
     fn new() -> Self {
         let here = Point::origin();
-        let mut open = PointSet::new();
-        open.insert(here);
+        let mut map = PointMap::new();
+        map.insert(here, Cell::Open);
         Self {
-            walls: PointSet::new(),
-            open,
+            map,
             here,
             trail: vec![],
             backtrack: None,
@@ -41,20 +47,18 @@ impl SearchDev {
     }
 
     fn print(&self) {
-        let (low, high) = self.walls.bounding_box();
-        for y in (low.y..=high.y).rev() {
-            let mut line = String::new();
-            for x in low.x..high.x {
-                let xy = Point { x, y };
-                let ch = if self.oxygen == Some(xy) { '$' }
-                else if self.here == xy { '@' }
-                else if self.walls.contains(xy) { '#' }
-                else if self.open.contains(xy) { '.' }
-                else { ' ' };
-                line.push(ch);
+        let view = View::new(self.map.bounding_box(), YAxis::Up);
+        print!("{}", view.to_text(|xy| {
+            if self.oxygen == Some(xy) { '$' }
+            else if self.here == xy { '@' }
+            else {
+                match self.map.get(xy) {
+                    Some(Cell::Wall) => '#',
+                    Some(Cell::Open) => '.',
+                    None => ' ',
+                }
             }
-            println!("{}", line);
-        }
+        }));
     }
 }
 
@@ -63,7 +67,7 @@ impl Device for SearchDev {
         // Explore?
         for &dir in DIRS {
             let unto = self.here + dir.to_move();
-            if self.walls.contains(unto) || self.open.contains(unto) {
+            if self.map.contains_key(unto) {
                 continue;
             }
             self.trail.push(dir);
@@ -88,11 +92,11 @@ impl Device for SearchDev {
         if val == 0 {
             assert!(self.backtrack.is_none());
             let attempt = self.trail.pop().unwrap().to_move();
-            assert!(self.walls.insert(self.here + attempt));
+            assert!(self.map.insert(self.here + attempt, Cell::Wall).is_none());
         } else if val <= 2 {
             let dir = self.backtrack.take().unwrap_or_else(|| *self.trail.last().unwrap());
             self.here += dir.to_move();
-            self.open.insert(self.here);
+            self.map.insert(self.here, Cell::Open);
             if val == 2 {
                 self.oxygen = Some(self.here);
             }
@@ -103,35 +107,6 @@ impl Device for SearchDev {
     }
 }
 
-fn distance_to(open: &PointSet, start: Point, there: Option<Point>) -> Option<usize> {
-    let mut open = open.clone();
-    assert!(open.remove(start));
-    let mut last = vec![start];
-    let mut dist = 1;
-    while !last.is_empty() {
-        let mut next = vec![];
-        for from in last {
-            for &dir in DIRS {
-                let unto = from + dir.to_move();
-                if Some(unto) == there {
-                    return Some(dist);
-                }
-                if !open.remove(unto) {
-                    continue;
-                }
-                next.push(unto);
-            }
-        }
-        last = next;
-        dist += 1;
-    }
-    if there.is_none() {
-        Some(dist - 2)
-    } else {
-        None
-    }
-}
-
 fn main() {
     let stdin = stdin();
     let prog = stdin.lock().lines().next().expect("no input").expect("I/O error reading stdin");
@@ -145,6 +120,7 @@ fn main() {
     };
     dev.print();
     let ox = dev.oxygen.expect("gasp!");
-    println!("Distance: {}", distance_to(&dev.open, Point::origin(), Some(ox)).expect("no path?"));
-    println!("Oxygen Time: {}", distance_to(&dev.open, ox, None).unwrap());
+    let open = |p| dev.map.get(p) == Some(&Cell::Open);
+    println!("Distance: {}", flood_fill(Point::origin(), open).get(ox).expect("no path?"));
+    println!("Oxygen Time: {}", flood_fill(ox, open).values().max().unwrap());
 }
@@ -2,43 +2,61 @@ use std::convert::TryInto;
 use std::io::{stdin, prelude::*};
 
 use intcode::{Computer, Device, IOError, Word};
+use painting::{Coord, Dir, Grid, Point, Turtle};
 
 #[derive(Debug, Clone)]
 struct CameraDev {
-    chars: Vec<Vec<u8>>,
+    text: String,
 }
 
 impl CameraDev {
     fn new() -> Self {
         Self {
-            chars: vec![vec![]],
+            text: String::new(),
         }
     }
 
-    fn trim(&mut self) {
-        while self.chars.last().map_or(false, |l| l.is_empty()) {
-            let _ = self.chars.pop();
-        }
+    fn grid(&self) -> Grid<char> {
+        Grid::from_ascii(self.text.lines(), |ch| ch).expect("ragged camera image")
     }
+}
 
-    fn find_isects(&self) -> Vec<(usize, usize)> {
-        assert!(!self.chars.is_empty());
-        let height = self.chars.len();
-        let width = self.chars[0].len();
-        assert!(self.chars.iter().all(|l| l.len() == width));
+fn find_isects(grid: &Grid<char>) -> Vec<Point> {
+    let is_scaffold = |p: Point| grid[p] == '#';
+    grid.points()
+        .filter(|&p| is_scaffold(p) && grid.neighbors4(p).filter(|&q| is_scaffold(q)).count() == 4)
+        .collect()
+}
 
-        let mut acc = vec![];
-        for y in 1..height-1 {
-            for x in 1..width-1 {
-                let is_cross = [(x-1, y), (x, y), (x+1, y), (x, y-1), (x, y+1)]
-                    .iter()
-                    .all(|&(xx, yy)| self.chars[yy][xx] == '#' as u8);
-                if is_cross {
-                    acc.push((x, y));
-                }
-            }
+// The route from the robot to the end of the scaffold, going straight
+// across intersections, in the robot's command format.
+fn scaffold_path(grid: &Grid<char>) -> String {
+    // The grid's y axis points down and the turtle's points up.
+    let flip = |p: Point| Point { x: p.x, y: -p.y };
+    let is_scaffold = |p: Point| grid.get(flip(p)).is_some_and(|&ch| ch != '.');
+    let (start, &robot) = grid.iter().find(|&(_p, ch)| "^v<>".contains(*ch)).expect("no robot");
+    let heading = match robot {
+        '^' => Dir::Up,
+        'v' => Dir::Dn,
+        '<' => Dir::Lf,
+        _ => Dir::Rt,
+    };
+    let mut turtle = Turtle::new(flip(start), heading);
+    let open = |t: &Turtle, d: Dir| is_scaffold(t.pos + d.to_move());
+    let h = turtle.heading;
+    if ![h, h.turn_left(), h.turn_right()].iter().any(|&d| open(&turtle, d)) {
+        turtle.reverse();
+    }
+    loop {
+        if is_scaffold(turtle.ahead()) {
+            turtle.forward(1);
+        } else if is_scaffold(turtle.pos + turtle.heading.turn_left().to_move()) {
+            turtle.left();
+        } else if is_scaffold(turtle.pos + turtle.heading.turn_right().to_move()) {
+            turtle.right();
+        } else {
+            return turtle.path();
         }
-        return acc;
     }
 }
 
@@ -46,11 +64,9 @@ impl Device for CameraDev {
     fn input(&mut self) -> Result<Word, IOError> { Err(IOError) }
 
     fn output(&mut self, val: Word) -> Result<(), IOError> {
-        Ok(if val == 10 {
-            self.chars.push(vec![]);
-        } else {
-            self.chars.last_mut().unwrap().push(val.try_into().map_err(|_| IOError)?);
-        })
+        let byte: u8 = val.try_into().map_err(|_| IOError)?;
+        self.text.push(byte as char);
+        Ok(())
     }
 }
 
@@ -61,11 +77,43 @@ fn main() {
 
     let mut dev = CameraDev::new();
     cpu.run(&mut dev).expect("runtime error");
-    for vline in &dev.chars {
-        let line: String = vline.iter().map(|&c| c as char).collect();
-        println!("{}", line);
+    print!("{}", dev.text);
+    let grid = dev.grid();
+    let align = find_isects(&grid);
+    println!("{}", align.iter().map(|p| p.x * p.y).sum::<Coord>());
+    println!("{}", scaffold_path(&grid));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn example() {
+        let mut dev = CameraDev::new();
+        for &b in b"..#..........\n..#..........\n#######...###\n#.#...#...#.#\n\
+                    #############\n..#...#...#..\n..#####...^..\n\n" {
+            dev.output(b as Word).unwrap();
+        }
+        let isects = find_isects(&dev.grid());
+        assert_eq!(isects.len(), 4);
+        assert_eq!(isects.iter().map(|p| p.x * p.y).sum::<Coord>(), 76);
+    }
+
+    #[test]
+    fn example_path() {
+        let art = ["#######...#####", "#.....#...#...#", "#.....#...#...#", "......#...#...#",
+                   "......#...###.#", "......#.....#.#", "^########...#.#", "......#.#...#.#",
+                   "......#########", "........#...#..", "....#########..", "....#...#......",
+                   "....#...#......", "....#...#......", "....#####......"];
+        let grid = Grid::from_ascii(&art, |ch| ch).unwrap();
+        assert_eq!(scaffold_path(&grid),
+                   "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2");
+    }
+
+    #[test]
+    fn facing_away() {
+        let grid = Grid::from_ascii(&["<###.", "...#."], |ch| ch).unwrap();
+        assert_eq!(scaffold_path(&grid), "R,R,3,R,1");
     }
-    dev.trim();
-    let align = dev.find_isects();
-    println!("{}", align.iter().map(|&(x, y)| x * y).sum::<usize>());
 }
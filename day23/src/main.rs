@@ -1,121 +1,50 @@
-use std::collections::VecDeque;
-use std::cell::RefCell;
-use std::io::{stdin, prelude::*};
+use std::env::args;
+use std::fs::File;
+use std::io::{stdin, prelude::*, BufWriter};
+use std::sync::{Arc, Mutex};
 
-use intcode::{Computer, Device, Word, IOError, exec::Stepped};
+use intcode::{Computer, Word};
+use intcode::net::{Framing, Network, Status, Topology};
+use intcode::pcap::{Capture, PcapWriter, Record};
+use intcode::sched::RoundRobin;
 
-struct NetQueue {
-    queue: VecDeque<Word>,
-    idling: bool,
-}
+const NAT_ADDR: usize = 255;
 
-impl NetQueue {
-    fn new() -> Self {
-        Self {
-            queue: VecDeque::new(),
-            idling: false
-        }
-    }
+type CapFile = Arc<Mutex<PcapWriter<BufWriter<File>>>>;
 
-    fn pop(&mut self) -> Word {
-        let maybe_word = self.queue.pop_front();
-        self.idling = maybe_word.is_none();
-        maybe_word.unwrap_or(-1)
-    }
-
-    fn push(&mut self, w: Word) {
-        self.idling = false;
-        self.queue.push_back(w);
-    }
-
-    fn is_idle(&self) -> bool {
-        self.idling
-    }
+struct Nat {
+    last: Option<(Word, Word)>,
+    capture: Option<CapFile>,
 }
 
-type Crossbar<'q> = &'q[RefCell<NetQueue>];
-
-struct NetDev<'q> {
-    crossbar: Crossbar<'q>,
-    cmd_buf: Vec<Word>,
-    addr: usize,
-}
-
-fn xlate_addr(w: Word) -> usize {
-    if w == 255 {
-        0
-    } else {
-        assert!(w >= 0 && w < 255);
-        (w as usize) + 1
-    }
-}
-
-impl<'q> NetDev<'q> {
-    fn new(crossbar: Crossbar<'q>, addr: Word) -> Self {
+impl Nat {
+    fn new(capture: Option<CapFile>) -> Self {
         Self {
-            crossbar,
-            cmd_buf: vec![],
-            addr: xlate_addr(addr)
-        }
-    }
-}
-
-impl<'q> Device for NetDev<'q> {
-    fn output(&mut self, val: Word) -> Result<(), IOError> {
-        debug_assert!(self.cmd_buf.len() < 3);
-        self.cmd_buf.push(val);
-        if self.cmd_buf.len() == 3 {
-            let dest = xlate_addr(self.cmd_buf[0]);
-            let mut queue = self.crossbar.get(dest).ok_or(IOError)?.borrow_mut();
-            let x = self.cmd_buf[1];
-            let y = self.cmd_buf[2];
-            self.cmd_buf.clear();
-            // eprintln!("{} -> {}: {} {}", self.addr, dest, x, y);
-            queue.push(x);
-            queue.push(y);
-        }
-        Ok(())
-    }
-
-    fn input(&mut self) -> Result<Word, IOError> {
-        Ok(self.crossbar[self.addr].borrow_mut().pop())
-    }
-}
-
-struct Nat<'q> {
-    crossbar: Crossbar<'q>,
-    size: Word,
-    last: Option<(Word, Word)>
-}
-
-impl<'q> Nat<'q> {
-    fn new(crossbar: Crossbar<'q>, size: Word) -> Self {
-        Self {
-            crossbar,
-            size,
             last: None,
+            capture,
         }
     }
 
-    fn tick(&mut self) {
-        let mut my_queue = self.crossbar[0].borrow_mut();
-        loop {
-            let x = my_queue.pop();
-            if x < 0 {
-                break;
-            }
-            let y = my_queue.pop();
+    fn tick(&mut self, net: &mut Network, status: Status) {
+        while let Some(pkt) = net.recv() {
+            assert_eq!(pkt.dst, NAT_ADDR, "CPU{} sent to nowhere", pkt.src);
+            let (x, y) = (pkt.data[0], pkt.data[1]);
             println!("NAT recv: {} {}", x, y);
             self.last = Some((x, y));
         }
-        if (0..self.size).all(|i| self.crossbar[xlate_addr(i)].borrow().is_idle()) {
-            let mut q0 = self.crossbar[xlate_addr(0)].borrow_mut();
-            if let &Some((x, y)) = &self.last {
+        if status == Status::Idle {
+            if let Some((x, y)) = self.last {
                 println!("NAT send: {} {}", x, y);
-                q0.push(x);
-                q0.push(y);
+                net.inject(0, &[x, y]);
+                if let Some(cap) = &self.capture {
+                    let rec = Record { tick: net.ticks(), src: NAT_ADDR as Word, dst: 0, data: vec![x, y] };
+                    cap.lock().unwrap().record(&rec);
+                }
             }
         }
+        if let Some(cap) = &self.capture {
+            cap.lock().unwrap().flush().expect("error writing capture");
+        }
     }
 }
 
@@ -124,23 +53,26 @@ fn main() {
     let prog = stdin.lock().lines().next().expect("no input").expect("I/O error reading stdin");
     let cpu = Computer::from_str(&prog).expect("parse error");
 
-    let crossbar: Vec<_> = (0..51).map(|_| RefCell::new(NetQueue::new())).collect();
-    let mut cpus: Vec<_> = (0..50).map(|_| cpu.clone()).collect();
-    let mut devs: Vec<_> = (0..50).map(|a| NetDev::new(&crossbar, a)).collect();
-    let mut nat = Nat::new(&crossbar, 50);
+    let framing = Framing::Addressed { len: 2, idle: Some(-1) };
+    let mut net = Network::new(&cpu, Topology::Crossbar(50), framing);
+    let capture = args().nth(1).map(|path| {
+        let file = File::create(path).expect("error creating capture file");
+        Arc::new(Mutex::new(PcapWriter::new(BufWriter::new(file)).expect("error writing capture")))
+    });
+    if let Some(cap) = &capture {
+        net.capture_to(cap.clone());
+    }
+    let mut nat = Nat::new(capture);
 
     for i in 0..50 {
-        crossbar[xlate_addr(i)].borrow_mut().push(i);
+        net.inject(i, &[i as Word]);
     }
 
+    // Nodes poll rather than block, so slices can be fairly long.
+    let mut policy = RoundRobin::new(64);
     loop {
-        for i in 0..50 {
-            match cpus[i].step(&mut devs[i]) {
-                Ok(Stepped::Ok) => (),
-                Ok(Stepped::Halted) => panic!("CPU{} halted", i),
-                e @ Err(_) => { e.expect(&format!("CPU{} fault", i)); }
-            }
-        }
-        nat.tick();
+        let status = net.run(&mut policy).unwrap_or_else(|e| panic!("network fault: {:?}", e));
+        assert_ne!(status, Status::Halted, "all CPUs halted");
+        nat.tick(&mut net, status);
     }
 }
@@ -1,8 +1,10 @@
-use std::borrow::Borrow;
 use std::collections::{HashSet, HashMap};
+use std::convert::TryFrom;
 use std::iter::IntoIterator;
 use std::ops::Deref;
 
+use painting::Grid as Picture;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Tile {
     x: u8, // 0..5
@@ -97,20 +99,14 @@ impl Grid {
         }
     }
 
-    pub fn add_line(&mut self, y: u8, z: i8, line: &str) {
-        for (x, ch) in line.chars().enumerate() {
-            if ch == '#' {
-                self.add(x as u8, y, z)
-            }
-        }
-    }
-
-    pub fn add_plane<Line, Plane>(&mut self, z: i8, plane: Plane)
-        where Line: Borrow<str>,
-              Plane: IntoIterator<Item = Line>
+    pub fn add_plane<I>(&mut self, z: i8, plane: I)
+        where I: IntoIterator,
+              I::Item: AsRef<str>
     {
-        for (y, line) in plane.into_iter().enumerate() {
-            self.add_line(y as u8, z, line.borrow())
+        let plane = Picture::from_ascii(plane, |ch| ch == '#').expect("ragged plane");
+        for (p, _) in plane.iter().filter(|&(_, &bug)| bug) {
+            let coord = |c| u8::try_from(c).expect("bad coordinates");
+            self.add(coord(p.x), coord(p.y), z)
         }
     }
 }
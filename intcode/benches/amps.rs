@@ -0,0 +1,164 @@
+// Compares running Intcode machines a thread apiece (as day 7 does)
+// with running them all on one async executor.  Run with `cargo bench`.
+
+use std::sync::mpsc;
+use std::time::Instant;
+
+use intcode::{Computer, Word, spawn_computer};
+use intcode::asynch::{self, Executor};
+use intcode::net::{Framing, Network, Status, Topology};
+
+// Day 7 part 2, example 2.
+const FEEDBACK: &[Word] = &[3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,
+                            -5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,
+                            53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10];
+
+// loop { out(in() + 1) }
+const INC: &[Word] = &[3,11,1001,11,1,11,4,11,1105,1,0];
+
+fn permutations(n: Word) -> Vec<Vec<Word>> {
+    if n == 0 {
+        return vec![vec![]];
+    }
+    let ps = permutations(n - 1);
+    let mut qs = vec![];
+    for i in 0..n {
+        for p in &ps {
+            qs.push(Some(i).into_iter().chain(p.iter().map(|&j| if j >= i { j + 1 } else { j })).collect());
+        }
+    }
+    qs
+}
+
+fn ring_net(cpu: &Computer, phases: &[Word]) -> Word {
+    let mut net = Network::new(cpu, Topology::Ring(phases.len()), Framing::Stream);
+    for (i, &phase) in phases.iter().enumerate() {
+        net.inject(i, &[phase + 5]);
+    }
+    net.inject(0, &[0]);
+    assert_eq!(net.run_threaded().unwrap(), Status::Halted);
+    *net.queue(0).back().unwrap()
+}
+
+fn ring_threads(cpu: &Computer, phases: &[Word]) -> Word {
+    let (sends, recvs): (Vec<_>, Vec<_>) = phases.iter().map(|_| mpsc::channel()).unzip();
+    for (send, &phase) in sends.iter().zip(phases) {
+        send.send(phase + 5).unwrap();
+    }
+    let (tap_s, tap_r) = mpsc::channel();
+    let joins: Vec<_> = recvs.into_iter()
+        .zip(sends.iter().skip(1).cloned().chain(Some(tap_s)))
+        .map(|(recv, send)| spawn_computer(cpu.clone(), recv, send))
+        .collect();
+    sends[0].send(0).unwrap();
+    let mut last = None;
+    for val in tap_r {
+        last = Some(val);
+        let _ = sends[0].send(val);
+    }
+    for join in joins {
+        join.join().unwrap().unwrap();
+    }
+    last.unwrap()
+}
+
+fn ring_async(cpu: &Computer, phases: &[Word]) -> Word {
+    let mut exec = Executor::new();
+    let (sends, recvs): (Vec<_>, Vec<_>) = phases.iter().map(|_| asynch::pipe()).unzip();
+    for (send, &phase) in sends.iter().zip(phases) {
+        send.send(phase + 5).unwrap();
+    }
+    sends[0].send(0).unwrap();
+    let (tap_s, mut tap_r) = asynch::pipe();
+    let outs = sends.iter().skip(1).cloned().chain(Some(tap_s));
+    for (recv, send) in recvs.into_iter().zip(outs) {
+        asynch::spawn_computer(&mut exec, cpu.clone(), recv, send);
+    }
+    let first = sends[0].clone();
+    drop(sends);
+    let last = exec.spawn(async move {
+        let mut last = None;
+        while let Ok(val) = tap_r.recv().await {
+            last = Some(val);
+            let _ = first.send(val);
+        }
+        last
+    });
+    assert_eq!(exec.run(), 0);
+    last.take().unwrap().unwrap()
+}
+
+fn chain_threads(cpu: &Computer, n: usize, inputs: &[Word]) -> Vec<Word> {
+    let (first, mut recv) = mpsc::channel();
+    let mut joins = vec![];
+    for _ in 0..n {
+        let (send, next) = mpsc::channel();
+        joins.push(spawn_computer(cpu.clone(), recv, send));
+        recv = next;
+    }
+    for &i in inputs {
+        first.send(i).unwrap();
+    }
+    drop(first);
+    let outs = recv.iter().collect();
+    for join in joins {
+        assert!(join.join().unwrap().is_err());
+    }
+    outs
+}
+
+fn chain_async(cpu: &Computer, n: usize, inputs: &[Word]) -> Vec<Word> {
+    let mut exec = Executor::new();
+    let (first, mut recv) = asynch::pipe();
+    for _ in 0..n {
+        let (send, next) = asynch::pipe();
+        asynch::spawn_computer(&mut exec, cpu.clone(), recv, send);
+        recv = next;
+    }
+    for &i in inputs {
+        first.send(i).unwrap();
+    }
+    drop(first);
+    let outs = exec.spawn(async move {
+        let mut outs = vec![];
+        while let Ok(val) = recv.recv().await {
+            outs.push(val);
+        }
+        outs
+    });
+    assert_eq!(exec.run(), 0);
+    outs.take().unwrap()
+}
+
+fn time<T: PartialEq + std::fmt::Debug>(name: &str, iters: u32, f: &dyn Fn() -> T) -> T {
+    let expected = f();
+    let start = Instant::now();
+    for _ in 0..iters {
+        assert_eq!(f(), expected);
+    }
+    let each = start.elapsed() / iters;
+    println!("{:24} {:>12?}", name, each);
+    expected
+}
+
+fn main() {
+    let feedback = Computer::new(FEEDBACK.to_vec());
+    let feedback = &feedback;
+    let best = |amp: fn(&Computer, &[Word]) -> Word| {
+        move || permutations(5).iter().map(|p| amp(feedback, p)).max().unwrap()
+    };
+    let iters = 20;
+    let a = time("day07 network threads", iters, &best(ring_net));
+    let b = time("day07 raw threads", iters, &best(ring_threads));
+    let c = time("day07 async", iters, &best(ring_async));
+    assert!(a == b && b == c && c == 18216);
+
+    let inc = Computer::new(INC.to_vec());
+    let inputs: Vec<Word> = (0..100).collect();
+    for &n in &[10, 100, 500] {
+        let t = time(&format!("chain of {} threads", n), 5, &|| chain_threads(&inc, n, &inputs));
+        let a = time(&format!("chain of {} async", n), 5, &|| chain_async(&inc, n, &inputs));
+        assert_eq!(t, a);
+        assert_eq!(a[0], n as Word);
+    }
+}
@@ -0,0 +1,366 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::{Computer, Device, ExecError, IOError, Word};
+use crate::decode::Opcode;
+use crate::exec::{ExecFault, Stepped};
+
+// Like Device, but input (and output) can wait without holding a
+// thread, so that many Computers can share one executor.
+pub trait AsyncDevice {
+    fn input(&mut self) -> impl Future<Output = Result<Word, IOError>>;
+    fn output(&mut self, val: Word) -> impl Future<Output = Result<(), IOError>>;
+}
+
+// Holds the one word of I/O that a single instruction can do, so that
+// the synchronous `step` can be reused.
+#[derive(Default)]
+struct Latch {
+    input: Option<Word>,
+    output: Option<Word>,
+}
+
+impl Device for Latch {
+    fn input(&mut self) -> Result<Word, IOError> {
+        self.input.take().ok_or(IOError)
+    }
+    fn output(&mut self, val: Word) -> Result<(), IOError> {
+        self.output = Some(val);
+        Ok(())
+    }
+}
+
+impl Computer {
+    // If output fails, the Computer has already moved past the
+    // instruction, but the error still names that instruction's pc.
+    // Extensions that do I/O fault instead of running, since there's no
+    // telling how many words they'd need.
+    pub async fn run_async<D: AsyncDevice>(&mut self, io: &mut D) -> Result<(), ExecError> {
+        loop {
+            let pc = self.pc();
+            let wants_input = match self.input_insn().map(|insn| insn.opcode) {
+                Some(Opcode::Ext { code, .. }) =>
+                    return Err(ExecError { pc, fault: ExecFault::Ext(code, "I/O in run_async") }),
+                Some(_) => true,
+                None => false,
+            };
+            let mut latch = Latch::default();
+            if wants_input {
+                latch.input = Some(io.input().await.map_err(|e| ExecError { pc, fault: e.into() })?);
+            }
+            let stepped = self.step(&mut latch)?;
+            if let Some(val) = latch.output {
+                io.output(val).await.map_err(|e| ExecError { pc, fault: e.into() })?;
+            }
+            if stepped == Stepped::Halted {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// An unbounded single-reader queue of words, for connecting Computers
+// on the same executor.  Reading fails once the queue is empty and the
+// writer is gone; writing fails once the reader is gone.
+struct Pipe {
+    queue: VecDeque<Word>,
+    waker: Option<Waker>,
+    writers: usize,
+    reader: bool,
+}
+
+pub struct PipeReader {
+    pipe: Rc<RefCell<Pipe>>,
+}
+
+pub struct PipeWriter {
+    pipe: Rc<RefCell<Pipe>>,
+}
+
+pub fn pipe() -> (PipeWriter, PipeReader) {
+    let pipe = Rc::new(RefCell::new(Pipe {
+        queue: VecDeque::new(),
+        waker: None,
+        writers: 1,
+        reader: true,
+    }));
+    (PipeWriter { pipe: pipe.clone() }, PipeReader { pipe })
+}
+
+impl PipeReader {
+    pub fn recv(&mut self) -> impl Future<Output = Result<Word, IOError>> + '_ {
+        Recv { pipe: &self.pipe }
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.pipe.borrow_mut().reader = false;
+    }
+}
+
+struct Recv<'p> {
+    pipe: &'p RefCell<Pipe>,
+}
+
+impl Future for Recv<'_> {
+    type Output = Result<Word, IOError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut pipe = self.pipe.borrow_mut();
+        if let Some(w) = pipe.queue.pop_front() {
+            Poll::Ready(Ok(w))
+        } else if pipe.writers == 0 {
+            Poll::Ready(Err(IOError))
+        } else {
+            pipe.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl PipeWriter {
+    pub fn send(&self, val: Word) -> Result<(), IOError> {
+        let mut pipe = self.pipe.borrow_mut();
+        if !pipe.reader {
+            return Err(IOError);
+        }
+        pipe.queue.push_back(val);
+        if let Some(waker) = pipe.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+}
+
+impl Clone for PipeWriter {
+    fn clone(&self) -> Self {
+        self.pipe.borrow_mut().writers += 1;
+        Self { pipe: self.pipe.clone() }
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        let mut pipe = self.pipe.borrow_mut();
+        pipe.writers -= 1;
+        if pipe.writers == 0 {
+            if let Some(waker) = pipe.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+// The async counterpart of ChannelDev.
+pub struct PipeDev {
+    recv: PipeReader,
+    send: PipeWriter,
+}
+
+impl PipeDev {
+    pub fn new(recv: PipeReader, send: PipeWriter) -> Self {
+        Self { recv, send }
+    }
+}
+
+impl AsyncDevice for PipeDev {
+    async fn input(&mut self) -> Result<Word, IOError> {
+        self.recv.recv().await
+    }
+    async fn output(&mut self, val: Word) -> Result<(), IOError> {
+        self.send.send(val)
+    }
+}
+
+type LocalTask = Pin<Box<dyn Future<Output = ()>>>;
+
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.id);
+    }
+}
+
+pub struct JoinHandle<T> {
+    result: Rc<RefCell<Option<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    // The task's result, if it has finished and hasn't been taken.
+    pub fn take(&self) -> Option<T> {
+        self.result.borrow_mut().take()
+    }
+}
+
+// A single-threaded executor: tasks are polled in the order they're
+// woken, and `run` returns when none are.
+pub struct Executor {
+    tasks: Vec<Option<LocalTask>>,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self { tasks: vec![], ready: Arc::new(Mutex::new(VecDeque::new())) }
+    }
+
+    pub fn spawn<T, F>(&mut self, fut: F) -> JoinHandle<T>
+        where T: 'static, F: Future<Output = T> + 'static
+    {
+        let result = Rc::new(RefCell::new(None));
+        let slot = result.clone();
+        let id = self.tasks.len();
+        self.tasks.push(Some(Box::pin(async move {
+            let val = fut.await;
+            *slot.borrow_mut() = Some(val);
+        })));
+        self.ready.lock().unwrap().push_back(id);
+        JoinHandle { result }
+    }
+
+    // Returns how many tasks are still unfinished, which is nonzero
+    // only if they're all waiting on something that will never happen.
+    pub fn run(&mut self) -> usize {
+        loop {
+            let next = self.ready.lock().unwrap().pop_front();
+            let id = match next {
+                Some(id) => id,
+                None => return self.tasks.iter().filter(|task| task.is_some()).count(),
+            };
+            let waker = Waker::from(Arc::new(TaskWaker { id, ready: self.ready.clone() }));
+            let mut cx = Context::from_waker(&waker);
+            if let Some(task) = self.tasks[id].as_mut() {
+                if task.as_mut().poll(&mut cx).is_ready() {
+                    self.tasks[id] = None;
+                }
+            }
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn spawn_computer(exec: &mut Executor, mut cpu: Computer, recv: PipeReader, send: PipeWriter)
+                      -> JoinHandle<Result<Computer, ExecError>>
+{
+    exec.spawn(async move {
+        cpu.run_async(&mut PipeDev::new(recv, send)).await?;
+        Ok(cpu)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use crate::isa::{ExtInsn, Isa};
+
+    #[test]
+    fn echo() {
+        let mut exec = Executor::new();
+        let (in_s, in_r) = pipe();
+        let (out_s, mut out_r) = pipe();
+        let cpu = spawn_computer(&mut exec, Computer::new(vec![3,0,4,0,99]), in_r, out_s);
+        in_s.send(0xDEADBEE).unwrap();
+        assert_eq!(exec.run(), 0);
+        assert_eq!(cpu.take().unwrap().unwrap().read(0), Ok(0xDEADBEE));
+        let out = exec.spawn(async move { out_r.recv().await });
+        exec.run();
+        assert_eq!(out.take(), Some(Ok(0xDEADBEE)));
+    }
+
+    #[test]
+    fn long_chain() {
+        // loop { out(in() + 1) }
+        let inc = Computer::new(vec![3,11,1001,11,1,11,4,11,1105,1,0]);
+        let mut exec = Executor::new();
+        let (first, mut recv) = pipe();
+        let mut cpus = vec![];
+        for _ in 0..200 {
+            let (send, next) = pipe();
+            cpus.push(spawn_computer(&mut exec, inc.clone(), recv, send));
+            recv = next;
+        }
+        let outs = exec.spawn(async move {
+            let mut outs = vec![];
+            while let Ok(val) = recv.recv().await {
+                outs.push(val);
+            }
+            outs
+        });
+        for i in 0..3 {
+            first.send(i).unwrap();
+        }
+        assert_eq!(exec.run(), 201);
+        drop(first);
+        assert_eq!(exec.run(), 0);
+        assert_eq!(outs.take(), Some(vec![200, 201, 202]));
+        for cpu in cpus {
+            assert_eq!(cpu.take().unwrap().err().unwrap().fault, ExecFault::IO(IOError));
+        }
+    }
+
+    #[test]
+    fn feedback_loop() {
+        // Day 7 part 2, example 1.
+        let prog = vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,
+                        27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+        let mut exec = Executor::new();
+        let (sends, recvs): (Vec<_>, Vec<_>) = (0..5).map(|_| pipe()).unzip();
+        for (send, &phase) in sends.iter().zip(&[9, 8, 7, 6, 5]) {
+            send.send(phase).unwrap();
+        }
+        sends[0].send(0).unwrap();
+        // The last amp's output is relayed, because the first amp will
+        // have halted by the time the final value comes around.
+        let (tap_s, mut tap_r) = pipe();
+        let outs = sends.iter().skip(1).cloned().chain(Some(tap_s));
+        let amps: Vec<_> = recvs.into_iter().zip(outs)
+            .map(|(recv, send)| spawn_computer(&mut exec, Computer::new(prog.clone()), recv, send))
+            .collect();
+        let first = sends[0].clone();
+        drop(sends);
+        let last = exec.spawn(async move {
+            let mut last = None;
+            while let Ok(val) = tap_r.recv().await {
+                last = Some(val);
+                let _ = first.send(val);
+            }
+            last
+        });
+        assert_eq!(exec.run(), 0);
+        assert_eq!(last.take(), Some(Some(139629729)));
+        assert!(amps.iter().all(|amp| amp.take().unwrap().is_ok()));
+    }
+
+    #[test]
+    fn extension_io() {
+        let mut isa = Isa::new();
+        isa.register(42, ExtInsn::new("echo", &[], |ctx| {
+            let val = ctx.input()?;
+            ctx.output(val)
+        }).with_io()).unwrap();
+        let mut exec = Executor::new();
+        let (in_s, in_r) = pipe();
+        let (out_s, _out_r) = pipe();
+        let cpu = Computer::new(vec![42,99]).with_isa(Arc::new(isa));
+        let cpu = spawn_computer(&mut exec, cpu, in_r, out_s);
+        in_s.send(1).unwrap();
+        exec.run();
+        assert_eq!(cpu.take().unwrap().err(),
+                   Some(ExecError { pc: 0, fault: ExecFault::Ext(42, "I/O in run_async") }));
+    }
+}
@@ -0,0 +1,13 @@
+use std::env::args;
+use std::fs::read_to_string;
+
+use intcode::parse;
+use intcode::decompile::decompile;
+
+// Usage: intcode-decompile PROGRAM
+fn main() {
+    let path = args().nth(1).expect("need a program file");
+    let mem = parse(&read_to_string(&path).expect("error reading program")).expect("parse error");
+    let code = decompile(&mem).unwrap_or_else(|e| panic!("can't analyze program: {:?}", e));
+    print!("{}", code);
+}
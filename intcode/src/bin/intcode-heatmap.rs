@@ -0,0 +1,38 @@
+use std::env::args;
+use std::fs::{File, read_to_string};
+use std::io::{BufWriter, stdout};
+
+use intcode::{Computer, parse};
+use intcode::heatmap::Heatmap;
+use intcode::sched::TapeDev;
+
+// Usage: intcode-heatmap PROGRAM [INPUT,INPUT,...] [ppm=FILE]
+//
+// Runs the program until it halts, faults or runs out of input, then
+// shows which memory it fetched (green), read (blue) and wrote (red).
+fn main() {
+    let mut args = args().skip(1);
+    let path = args.next().expect("need a program file");
+    let mem = parse(&read_to_string(&path).expect("error reading program")).expect("parse error");
+    let mut input = vec![];
+    let mut ppm = None;
+    for arg in args {
+        match arg.strip_prefix("ppm=") {
+            Some(file) => ppm = Some(file.to_owned()),
+            None => input = parse(&arg).expect("bad input list"),
+        }
+    }
+
+    let mut map = Heatmap::new();
+    let mut cpu = Computer::new(mem);
+    let mut dev = TapeDev::new(&input);
+    if let Err(err) = map.run(&mut cpu, &mut dev) {
+        eprintln!("stopped: {:?}", err);
+    }
+    eprintln!("{} outputs", dev.output.len());
+    map.write_ansi(stdout().lock()).expect("error writing map");
+    if let Some(file) = ppm {
+        let out = BufWriter::new(File::create(&file).expect("error creating image"));
+        map.write_ppm(out, 4).expect("error writing image");
+    }
+}
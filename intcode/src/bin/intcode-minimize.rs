@@ -0,0 +1,29 @@
+use std::env::args;
+use std::fs::read_to_string;
+
+use intcode::parse;
+use intcode::minimize::{Case, Ending, minimize, same_fault};
+
+const STEP_LIMIT: usize = 1_000_000;
+
+// Usage: intcode-minimize PROGRAM [INPUT,INPUT,...]
+//
+// Shrinks a program (and its input) that faults down to a small one
+// that faults the same way, and prints it as a test.  Other kinds of
+// misbehavior need a predicate; see intcode::minimize.
+fn main() {
+    let mut args = args().skip(1);
+    let path = args.next().expect("need a program file");
+    let mem = parse(&read_to_string(&path).expect("error reading program")).expect("parse error");
+    let input = args.next().map_or(vec![], |s| parse(&s).expect("bad input list"));
+    let case = Case::new(mem, input);
+
+    let outcome = case.run(STEP_LIMIT);
+    let fault = match outcome.ending {
+        Ending::Fault(fault) => fault,
+        ending => panic!("program didn't fault: {:?}", ending),
+    };
+    eprintln!("minimizing {} words and {} inputs for {:?}", case.mem.len(), case.input.len(), fault);
+    let min = minimize(&case, same_fault(fault, STEP_LIMIT));
+    print!("{}", min.to_test("minimized", &min.run(STEP_LIMIT)));
+}
@@ -0,0 +1,37 @@
+use std::env::args;
+use std::fs::read_to_string;
+use std::process::exit;
+
+use intcode::parse;
+use intcode::opt::{optimize, validate, Invalid};
+
+const STEP_LIMIT: usize = 100_000_000;
+
+// Usage: intcode-opt PROGRAM [INPUT,INPUT,...]...
+//
+// Prints the optimized image, after checking that it behaves the same
+// as the original on each of the given input tapes (or on no input, if
+// none are given).
+fn main() {
+    let mut args = args().skip(1);
+    let path = args.next().expect("need a program file");
+    let orig = parse(&read_to_string(&path).expect("error reading program")).expect("parse error");
+    let mut tapes: Vec<_> = args.map(|s| parse(&s).expect("bad input list")).collect();
+    if tapes.is_empty() {
+        tapes.push(vec![]);
+    }
+
+    let (opt, report) = optimize(&orig).unwrap_or_else(|e| panic!("can't analyze program: {:?}", e));
+    eprintln!("{} -> {} words; {:?}", orig.len(), opt.len(), report);
+    match validate(&orig, &opt, &tapes, STEP_LIMIT) {
+        Ok(()) => (),
+        Err(Invalid::Mismatch(m)) => {
+            eprintln!("optimized program differs on input {:?}:\n  before: {:?}\n  after: {:?}",
+                      m.input, m.before, m.after);
+            exit(1);
+        }
+        Err(Invalid::NoTapes) => unreachable!(),
+    }
+    let words: Vec<_> = opt.iter().map(|w| w.to_string()).collect();
+    println!("{}", words.join(","));
+}
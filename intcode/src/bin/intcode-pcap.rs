@@ -0,0 +1,38 @@
+use std::env::args;
+use std::fs::File;
+use std::io::BufReader;
+use std::str::FromStr;
+
+use intcode::pcap::{Filter, PcapReader};
+
+// Usage: intcode-pcap FILE [src=N] [dst=N] [from=TICK] [to=TICK]
+fn main() {
+    let mut args = args().skip(1);
+    let path = args.next().expect("need a capture file");
+    let mut filter = Filter::default();
+    let (mut from, mut to) = (None, None);
+    for arg in args {
+        let (key, val) = arg.split_at(arg.find('=').expect("filters look like key=value"));
+        let val = &val[1..];
+        match key {
+            "src" => filter.src = Some(FromStr::from_str(val).expect("bad src")),
+            "dst" => filter.dst = Some(FromStr::from_str(val).expect("bad dst")),
+            "from" => from = Some(u64::from_str(val).expect("bad tick")),
+            "to" => to = Some(u64::from_str(val).expect("bad tick")),
+            _ => panic!("unknown filter {:?}", key),
+        }
+    }
+    if from.is_some() || to.is_some() {
+        filter.ticks = Some(from.unwrap_or(0)..=to.unwrap_or(u64::MAX));
+    }
+
+    let file = File::open(&path).expect("error opening capture");
+    let reader = PcapReader::new(BufReader::new(file)).expect("error reading capture");
+    for rec in reader {
+        let rec = rec.expect("error reading capture");
+        if filter.matches(&rec) {
+            let data: Vec<_> = rec.data.iter().map(|w| w.to_string()).collect();
+            println!("{}\t{} -> {}: {}", rec.tick, rec.src, rec.dst, data.join(" "));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env::args;
+use std::fs::read_to_string;
+
+use intcode::{Computer, Stepped, Word, parse};
+use intcode::callstack::CallStack;
+use intcode::decode::{Insn, Mode};
+use intcode::disasm::disasm;
+use intcode::exec::ExecFault;
+use intcode::sched::TapeDev;
+
+const STEP_LIMIT: u64 = 1 << 32;
+
+fn mode_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Position => "position",
+        Mode::Immediate => "immediate",
+        Mode::Relative => "relative",
+    }
+}
+
+fn print_counts<K: std::fmt::Display>(title: &str, counts: &BTreeMap<K, u64>) {
+    println!("{}:", title);
+    for (k, n) in counts {
+        println!("  {:10} {}", k, n);
+    }
+}
+
+fn static_stats(mem: &[Word]) {
+    println!("length: {} words", mem.len());
+    let valid = mem.iter().filter(|&&w| Insn::decode(w).is_ok()).count();
+    println!("decodable: {} words", valid);
+
+    // Operand statistics are for a linear sweep, so they can be thrown
+    // off by data in among the code.
+    let lines = disasm(mem, None);
+    let mut opcodes = BTreeMap::new();
+    let mut modes = BTreeMap::new();
+    let mut consts = BTreeSet::new();
+    let mut max_addr = None;
+    let mut data = 0;
+    for line in &lines {
+        let insn = match Insn::decode(mem[line.addr as usize]) {
+            Ok(insn) => insn,
+            Err(_) => { data += 1; continue }
+        };
+        *opcodes.entry(insn.opcode.mnemonic()).or_insert(0) += 1;
+        for i in 0..line.len as usize - 1 {
+            let field = mem.get(line.addr as usize + 1 + i).copied().unwrap_or(0);
+            *modes.entry(mode_name(insn.modes[i])).or_insert(0) += 1;
+            match insn.modes[i] {
+                Mode::Immediate => { consts.insert(field); }
+                Mode::Position => max_addr = max_addr.max(Some(field)),
+                Mode::Relative => (),
+            }
+        }
+    }
+    println!("instructions: {} (and {} undecodable words) in a linear sweep", lines.len() - data, data);
+    print_counts("opcodes", &opcodes);
+    print_counts("modes", &modes);
+    let consts: Vec<_> = consts.iter().map(|w| w.to_string()).collect();
+    println!("constants: {} distinct: {}", consts.len(), consts.join(" "));
+    if let Some(addr) = max_addr {
+        println!("max position address: {}{}", addr,
+                 if addr as usize >= mem.len() { " (beyond the image)" } else { "" });
+    }
+}
+
+fn dynamic_stats(mem: &[Word], input: &[Word]) {
+    let mut cpu = Computer::new(mem.to_vec());
+    let mut dev = TapeDev::new(input);
+    let mut stack = CallStack::new();
+    let mut backtrace = None;
+    let mut opcodes = BTreeMap::new();
+    let mut steps = 0;
+    let ending = loop {
+        if steps == STEP_LIMIT {
+            break "still running".to_owned();
+        }
+        let opcode = cpu.read(cpu.pc()).ok().and_then(|w| Insn::decode(w).ok()).map(|insn| insn.opcode);
+        match stack.step(&mut cpu, &mut dev) {
+            Ok(stepped) => {
+                steps += 1;
+                *opcodes.entry(opcode.unwrap().mnemonic()).or_insert(0) += 1;
+                if stepped == Stepped::Halted {
+                    break "halted".to_owned();
+                }
+            }
+            Err(err) if err.fault == ExecFault::IO(intcode::IOError) =>
+                break format!("out of input at pc {}", err.pc),
+            Err(err) => {
+                backtrace = Some(stack.backtrace(err.pc));
+                break format!("faulted: {:?}", err);
+            }
+        }
+    };
+    println!("run: {} after {} steps", ending, steps);
+    if let Some(backtrace) = backtrace {
+        println!("{}", backtrace);
+    }
+    println!("inputs used: {} of {}", input.len() - dev.input.len(), input.len());
+    println!("outputs: {}", dev.output.len());
+    println!("memory: {} words", cpu.mem_len());
+    print_counts("executed", &opcodes);
+}
+
+// Usage: intcode-stat PROGRAM [INPUT,INPUT,...]
+fn main() {
+    let mut args = args().skip(1);
+    let path = args.next().expect("need a program file");
+    let mem = parse(&read_to_string(&path).expect("error reading program")).expect("parse error");
+    static_stats(&mem);
+    if let Some(input) = args.next() {
+        println!();
+        dynamic_stats(&mem, &parse(&input).expect("bad input list"));
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+
+use crate::{Computer, Device, ExecError, Stepped, Word};
+use crate::decode::{Insn, Mode, Opcode};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub entry: Word,
+    pub call_site: Word,
+    pub ret: Word,
+    // The relative base when the call was made, and how far the callee
+    // has moved it since.
+    pub base: Word,
+    pub size: Word,
+}
+
+// Follows the usual compiled-Intcode calling convention: the caller
+// stores the return address in a stack slot and jumps to the callee,
+// which later jumps back through that slot.  A jump whose target was
+// just stored relative to the base is taken as a call, and a jump
+// through a stack slot to a pending return address as a return (from
+// that frame and any above it).
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+    // Values stored in stack slots since the last jump.
+    stored: Vec<Word>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TracedError {
+    pub error: ExecError,
+    pub frames: Vec<Frame>,
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn step(&mut self, cpu: &mut Computer, io: &mut dyn Device) -> Result<Stepped, ExecError> {
+        let (pc, base) = (cpu.pc(), cpu.base());
+        let insn = cpu.read(pc).ok().and_then(|w| Insn::decode(w).ok());
+        let field = |i: Word| cpu.read(pc + 1 + i).unwrap_or(0);
+        // An address that overflows has no effect here; the CPU will fault.
+        let slot = match &insn {
+            Some(insn) => match insn.opcode {
+                Opcode::Add | Opcode::Mul | Opcode::CmpLt | Opcode::CmpEq if insn.modes[2] == Mode::Relative =>
+                    base.checked_add(field(2)),
+                Opcode::In if insn.modes[0] == Mode::Relative => base.checked_add(field(0)),
+                _ => None,
+            },
+            None => None,
+        };
+        let jump = insn.as_ref()
+            .filter(|insn| insn.opcode == Opcode::Jnz || insn.opcode == Opcode::Jz)
+            .map(|insn| insn.modes[1] == Mode::Relative);
+
+        let stepped = cpu.step(io)?;
+
+        if let Some(slot) = slot {
+            self.stored.push(cpu.read(slot).unwrap_or(0));
+        }
+        if let Some(top) = self.frames.last_mut() {
+            if let Some(size) = cpu.base().checked_sub(base).and_then(|delta| top.size.checked_add(delta)) {
+                top.size = size;
+            }
+        }
+        if let Some(through_stack) = jump {
+            let target = cpu.pc();
+            if target != pc + 3 {
+                let ret = if through_stack { self.frames.iter().rposition(|f| f.ret == target) } else { None };
+                if let Some(i) = ret {
+                    self.frames.truncate(i);
+                } else if self.stored.contains(&(pc + 3)) {
+                    self.frames.push(Frame { entry: target, call_site: pc, ret: pc + 3, base, size: 0 });
+                }
+            }
+            self.stored.clear();
+        }
+        Ok(stepped)
+    }
+
+    pub fn run(&mut self, cpu: &mut Computer, io: &mut dyn Device) -> Result<(), TracedError> {
+        loop {
+            match self.step(cpu, io) {
+                Ok(Stepped::Ok) => (),
+                Ok(Stepped::Halted) => return Ok(()),
+                Err(error) => return Err(TracedError { error, frames: self.frames.clone() }),
+            }
+        }
+    }
+
+    // Innermost first, like a debugger's backtrace.
+    pub fn backtrace(&self, pc: Word) -> String {
+        backtrace(&self.frames, pc)
+    }
+}
+
+fn backtrace(frames: &[Frame], pc: Word) -> String {
+    let mut lines = vec![];
+    let mut at = pc;
+    for (i, f) in frames.iter().enumerate().rev() {
+        lines.push(format!("#{} pc {} in f{} (frame {})", frames.len() - i - 1, at, f.entry, f.size));
+        at = f.call_site;
+    }
+    lines.push(format!("#{} pc {} in f0", frames.len(), at));
+    lines.join("\n")
+}
+
+impl fmt::Display for TracedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:?} at pc {}", self.error.fault, self.error.pc)?;
+        write!(f, "{}", backtrace(&self.frames, self.error.pc))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exec::{ArithOp, ExecFault};
+    use crate::sched::TapeDev;
+
+    // Counts m[40] down from the input, calling f22 (which outputs it)
+    // each time; f22 has a frame of 3.
+    const COUNTDOWN: &[Word] = &[109,1,3,40,1006,40,21,21101,14,0,0,1105,1,22,1001,40,-1,40,1105,1,4,
+                                 99,109,3,4,40,109,-3,2106,0,0];
+
+    #[test]
+    fn calls_and_returns() {
+        let mut cs = CallStack::new();
+        let mut cpu = Computer::new(COUNTDOWN.to_vec());
+        let mut dev = TapeDev::new(&[2]);
+        let mut seen = vec![];
+        while cs.step(&mut cpu, &mut dev).unwrap() == Stepped::Ok {
+            seen.push((cpu.pc(), cs.frames().iter().map(|f| (f.entry, f.size)).collect::<Vec<_>>()));
+        }
+        assert_eq!(dev.output, vec![2, 1]);
+        assert_eq!(cs.depth(), 0);
+        let inside: Vec<_> = seen.iter().filter(|(pc, _)| *pc == 24).collect();
+        assert_eq!(inside.len(), 2);
+        assert!(inside.iter().all(|(_, frames)| *frames == vec![(22, 3)]));
+        assert!(seen.iter().filter(|(pc, _)| *pc == 14).all(|(_, frames)| frames.is_empty()));
+    }
+
+    #[test]
+    fn fault_in_callee() {
+        // As above, but f22 calls f38, which reads m[-1].
+        let mut prog = COUNTDOWN.to_vec();
+        prog.truncate(24);
+        prog.extend(&[21101,31,0,0,1105,1,38, 4,40,109,-3,2106,0,0, 4,-1]);
+        let mut cpu = Computer::new(prog);
+        let err = CallStack::new().run(&mut cpu, &mut TapeDev::new(&[5])).unwrap_err();
+        assert_eq!(err.error.pc, 38);
+        assert_eq!(err.frames.iter().map(|f| (f.entry, f.call_site)).collect::<Vec<_>>(),
+                   vec![(22, 11), (38, 28)]);
+        assert_eq!(err.to_string(), "\
+Mem(MemFault { addr: -1, mode: DRead }) at pc 38
+#0 pc 38 in f38 (frame 0)
+#1 pc 28 in f22 (frame 3)
+#2 pc 11 in f0");
+    }
+
+    #[test]
+    fn overflowing_slot() {
+        // rb = 1, then store and input through [rb+MAX].
+        for prog in &[vec![109,1,21101,1,2,Word::MAX,99], vec![109,1,203,Word::MAX,99]] {
+            let mut cpu = Computer::new(prog.clone());
+            let err = CallStack::new().run(&mut cpu, &mut TapeDev::new(&[5])).unwrap_err();
+            assert_eq!(err.error.fault, ExecFault::Overflow(ArithOp::Lea, 1, Word::MAX));
+            assert!(err.frames.is_empty());
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::Word;
+use crate::decode::{Insn, Mode, Opcode};
+
+// How control leaves a basic block.  A jump's target is known only if
+// it's an immediate operand that nothing overwrites; likewise for its
+// condition, and a jump whose condition is known is an unconditional
+// Jump or no jump at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Fall(Word),
+    Jump(Word),
+    Branch { taken: Word, fall: Word },
+    Indirect { fall: Option<Word> },
+    Halt,
+    Invalid,
+}
+
+impl Exit {
+    pub fn succs(self) -> Vec<Word> {
+        match self {
+            Exit::Fall(next) | Exit::Jump(next) => vec![next],
+            Exit::Branch { taken, fall } => vec![taken, fall],
+            Exit::Indirect { fall } => fall.into_iter().collect(),
+            Exit::Halt | Exit::Invalid => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: Word,
+    // The address of each instruction; the last one's exit is the block's.
+    pub insns: Vec<Word>,
+    pub end: Word,
+    pub exit: Exit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfgError {
+    // An instruction starts inside another reachable instruction.
+    Overlap(Word),
+    // A reachable instruction's opcode word is overwritten.
+    SelfModifying(Word),
+}
+
+// The program is explored from address 0.  If there are indirect jumps,
+// any constant in the image that's the address of an instruction is
+// taken as a possible target.  Stores to addresses that aren't
+// constants (relative mode, or a position operand that's itself
+// overwritten) are assumed not to hit code, as in compiled Intcode.
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub blocks: BTreeMap<Word, Block>,
+    // Constant addresses of position-mode stores by reachable code.
+    pub written: BTreeSet<Word>,
+    // Likewise for loads, including jump targets fetched from memory.
+    pub read: BTreeSet<Word>,
+    pub indirect: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    insn: Option<Insn>,
+    len: Word,
+    exit: Option<Exit>,
+}
+
+fn word(mem: &[Word], addr: Word) -> Word {
+    if addr < 0 { 0 } else { mem.get(addr as usize).copied().unwrap_or(0) }
+}
+
+fn mode_code(mode: Mode) -> Word {
+    match mode {
+        Mode::Position => 0,
+        Mode::Immediate => 1,
+        Mode::Relative => 2,
+    }
+}
+
+// Puts an instruction word back together.
+pub fn encode(opcode: Word, modes: [Mode; 3]) -> Word {
+    opcode + 100 * mode_code(modes[0]) + 1000 * mode_code(modes[1]) + 10000 * mode_code(modes[2])
+}
+
+// An operand whose value is known without running anything.
+pub fn constant(mem: &[Word], written: &BTreeSet<Word>, insn: &Insn, addr: Word, idx: usize) -> Option<Word> {
+    let field = addr + 1 + idx as Word;
+    if insn.modes[idx] == Mode::Immediate && !written.contains(&field) {
+        Some(word(mem, field))
+    } else {
+        None
+    }
+}
+
+fn node(mem: &[Word], written: &BTreeSet<Word>, addr: Word) -> Node {
+    let insn = match Insn::decode(word(mem, addr)) {
+        Ok(insn) if addr >= 0 => insn,
+        _ => return Node { insn: None, len: 1, exit: Some(Exit::Invalid) },
+    };
+    let len = insn.opcode.len();
+    let next = match addr.checked_add(len) {
+        Some(next) => next,
+        None => return Node { insn: None, len: 1, exit: Some(Exit::Invalid) },
+    };
+    let exit = match insn.opcode {
+        Opcode::Halt => Some(Exit::Halt),
+        Opcode::Jnz | Opcode::Jz => {
+            let target = constant(mem, written, &insn, addr, 1);
+            let taken = constant(mem, written, &insn, addr, 0)
+                .map(|c| (c != 0) == (insn.opcode == Opcode::Jnz));
+            Some(match (taken, target) {
+                (Some(false), _) => Exit::Fall(next),
+                (Some(true), Some(t)) => Exit::Jump(t),
+                (Some(true), None) => Exit::Indirect { fall: None },
+                (None, Some(t)) => Exit::Branch { taken: t, fall: next },
+                (None, None) => Exit::Indirect { fall: Some(next) },
+            })
+        }
+        _ => None,
+    };
+    Node { insn: Some(insn), len, exit }
+}
+
+fn succs(node: &Node, addr: Word) -> Vec<Word> {
+    match node.exit {
+        Some(exit) => exit.succs(),
+        None => addr.checked_add(node.len).into_iter().collect(),
+    }
+}
+
+// Every instruction reachable from `roots`, checking for overlaps.
+fn explore(mem: &[Word], written: &BTreeSet<Word>, roots: &BTreeSet<Word>)
+           -> Result<BTreeMap<Word, Node>, CfgError>
+{
+    let mut nodes = BTreeMap::new();
+    let mut todo: Vec<_> = roots.iter().copied().collect();
+    while let Some(addr) = todo.pop() {
+        if nodes.contains_key(&addr) {
+            continue;
+        }
+        let n = node(mem, written, addr);
+        if written.contains(&addr) {
+            return Err(CfgError::SelfModifying(addr));
+        }
+        let before = nodes.range(..addr).next_back();
+        // Nothing at `addr` yet, so anything in the range starts after it.
+        if before.is_some_and(|(&a, b): (&Word, &Node)| a.saturating_add(b.len) > addr)
+            || nodes.range(addr..addr.saturating_add(n.len)).next().is_some()
+        {
+            return Err(CfgError::Overlap(addr));
+        }
+        todo.extend(succs(&n, addr));
+        nodes.insert(addr, n);
+    }
+    Ok(nodes)
+}
+
+fn stores(mem: &[Word], written: &BTreeSet<Word>, nodes: &BTreeMap<Word, Node>) -> BTreeSet<Word> {
+    let mut out = BTreeSet::new();
+    for (&addr, node) in nodes {
+        let insn = match &node.insn {
+            Some(insn) => insn,
+            None => continue,
+        };
+        let idx = match insn.opcode {
+            Opcode::Add | Opcode::Mul | Opcode::CmpLt | Opcode::CmpEq => 2,
+            Opcode::In => 0,
+            _ => continue,
+        };
+        let field = addr + 1 + idx as Word;
+        if insn.modes[idx] == Mode::Position && !written.contains(&field) {
+            out.insert(word(mem, field));
+        }
+    }
+    out
+}
+
+fn loads(mem: &[Word], written: &BTreeSet<Word>, nodes: &BTreeMap<Word, Node>) -> BTreeSet<Word> {
+    let mut out = BTreeSet::new();
+    for (&addr, node) in nodes {
+        let insn = match &node.insn {
+            Some(insn) => insn,
+            None => continue,
+        };
+        let n = match insn.opcode {
+            Opcode::Add | Opcode::Mul | Opcode::CmpLt | Opcode::CmpEq | Opcode::Jnz | Opcode::Jz => 2,
+            Opcode::Out | Opcode::SetBase => 1,
+            _ => 0,
+        };
+        for idx in 0..n {
+            let field = addr + 1 + idx as Word;
+            if insn.modes[idx] == Mode::Position && !written.contains(&field) {
+                out.insert(word(mem, field));
+            }
+        }
+    }
+    out
+}
+
+impl Cfg {
+    pub fn build(mem: &[Word]) -> Result<Self, CfgError> {
+        let sweep: BTreeSet<_> = crate::disasm::disasm(mem, None).iter().map(|l| l.addr).collect();
+        let mut written = BTreeSet::new();
+        let mut roots: BTreeSet<Word> = Some(0).into_iter().collect();
+        loop {
+            let nodes = explore(mem, &written, &roots)?;
+            let indirect = nodes.values().any(|n| matches!(n.exit, Some(Exit::Indirect { .. })));
+            let mut new_roots = roots.clone();
+            if indirect {
+                new_roots.extend(mem.iter().copied()
+                                 .filter(|w| sweep.contains(w) || nodes.contains_key(w)));
+            }
+            // Only ever growing, or a store that overwrites its own
+            // address field would flip back and forth.
+            let mut new_written = written.clone();
+            new_written.extend(stores(mem, &written, &nodes));
+            if new_roots == roots && new_written == written {
+                let read = loads(mem, &written, &nodes);
+                return Ok(Self::from_nodes(&nodes, &roots, written, read, indirect));
+            }
+            roots = new_roots;
+            written = new_written;
+        }
+    }
+
+    fn from_nodes(nodes: &BTreeMap<Word, Node>, roots: &BTreeSet<Word>, written: BTreeSet<Word>,
+                  read: BTreeSet<Word>, indirect: bool) -> Self
+    {
+        let mut leaders = roots.clone();
+        for (&addr, node) in nodes {
+            if node.exit.is_some() {
+                leaders.extend(succs(node, addr));
+            }
+        }
+        let mut blocks = BTreeMap::new();
+        for &start in leaders.iter().filter(|a| nodes.contains_key(a)) {
+            let mut insns = vec![];
+            let mut addr = start;
+            let exit = loop {
+                let node = &nodes[&addr];
+                insns.push(addr);
+                if let Some(exit) = node.exit {
+                    break exit;
+                }
+                addr += node.len;
+                if leaders.contains(&addr) {
+                    break Exit::Fall(addr);
+                }
+            };
+            let last = *insns.last().unwrap();
+            let end = last.saturating_add(nodes[&last].len);
+            blocks.insert(start, Block { start, insns, end, exit });
+        }
+        Self { blocks, written, read, indirect }
+    }
+
+    pub fn block(&self, start: Word) -> Option<&Block> {
+        self.blocks.get(&start)
+    }
+
+    // Whether `addr` is part of some reachable instruction.
+    pub fn covers(&self, addr: Word) -> bool {
+        self.blocks.range(..=addr).next_back().is_some_and(|(_, b)| addr < b.end)
+    }
+
+    pub fn preds(&self) -> BTreeMap<Word, Vec<Word>> {
+        let mut preds: BTreeMap<Word, Vec<Word>> = BTreeMap::new();
+        for block in self.blocks.values() {
+            for succ in block.exit.succs() {
+                preds.entry(succ).or_default().push(block.start);
+            }
+        }
+        preds
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn exits(cfg: &Cfg) -> Vec<(Word, Word, Exit)> {
+        cfg.blocks.values().map(|b| (b.start, b.end, b.exit)).collect()
+    }
+
+    #[test]
+    fn branches() {
+        // Day 5's "compare to 8" example.
+        let mem = [3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+                   1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+                   999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99];
+        let cfg = Cfg::build(&mem).unwrap();
+        assert_eq!(exits(&cfg), vec![
+            (0, 9, Exit::Branch { taken: 22, fall: 9 }),
+            (9, 16, Exit::Branch { taken: 31, fall: 16 }),
+            (16, 19, Exit::Jump(36)),
+            (22, 31, Exit::Jump(46)),
+            (31, 36, Exit::Jump(46)),
+            (36, 45, Exit::Jump(46)),
+            (46, 47, Exit::Halt),
+        ]);
+        assert!(!cfg.indirect);
+        assert_eq!(cfg.written, [20, 21].iter().copied().collect());
+        assert_eq!(cfg.read, [20, 21].iter().copied().collect());
+        assert!(cfg.covers(44) && !cfg.covers(45) && !cfg.covers(19) && !cfg.covers(47));
+        assert_eq!(cfg.preds()[&46], vec![22, 31, 36]);
+    }
+
+    #[test]
+    fn indirect() {
+        // Calls a subroutine at 10 with the return address on the stack.
+        let mem = [109,100,21101,9,0,0,1105,1,10,99,104,1,2106,0,0];
+        let cfg = Cfg::build(&mem).unwrap();
+        assert!(cfg.indirect);
+        assert_eq!(exits(&cfg), vec![
+            (0, 9, Exit::Jump(10)),
+            (9, 10, Exit::Halt),
+            (10, 15, Exit::Indirect { fall: None }),
+        ]);
+    }
+
+    #[test]
+    fn errors() {
+        // Day 2's example overwrites its own first instruction.
+        assert_eq!(Cfg::build(&[1,9,10,3,2,3,11,0,99,30,40,50]).unwrap_err(), CfgError::SelfModifying(0));
+        assert_eq!(Cfg::build(&[1105,1,2,99]).unwrap_err(), CfgError::Overlap(2));
+        assert_eq!(Cfg::build(&[1003,2,0]).unwrap_err(), CfgError::SelfModifying(2));
+        // Stores to its own address field.
+        assert_eq!(Cfg::build(&[1,0,0,3,99]).unwrap().written, [3].iter().copied().collect());
+    }
+
+    #[test]
+    fn far_jump() {
+        let cfg = Cfg::build(&[1105,1,Word::MAX]).unwrap();
+        assert_eq!(exits(&cfg), vec![
+            (0, 3, Exit::Jump(Word::MAX)),
+            (Word::MAX, Word::MAX, Exit::Invalid),
+        ]);
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::{Computer, Device, ExecError, IOError, Word};
+
+// A Device that reads from one channel and writes to another, for
+// running Computers on their own threads.  If the peer on either end
+// hangs up (or a read times out) the I/O fails, so the Computer stops
+// with an IO fault instead of the thread panicking or hanging forever.
+#[derive(Debug)]
+pub struct ChannelDev {
+    recv: Receiver<Word>,
+    send: Sender<Word>,
+    timeout: Option<Duration>,
+}
+
+impl ChannelDev {
+    pub fn new(recv: Receiver<Word>, send: Sender<Word>) -> Self {
+        Self { recv, send, timeout: None }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout: Some(timeout), ..self }
+    }
+
+    pub fn into_inner(self) -> (Receiver<Word>, Sender<Word>) {
+        (self.recv, self.send)
+    }
+}
+
+impl Device for ChannelDev {
+    fn input(&mut self) -> Result<Word, IOError> {
+        match self.timeout {
+            None => self.recv.recv().map_err(|_| IOError),
+            Some(timeout) => self.recv.recv_timeout(timeout).map_err(|_| IOError),
+        }
+    }
+
+    fn output(&mut self, val: Word) -> Result<(), IOError> {
+        self.send.send(val).map_err(|_| IOError)
+    }
+}
+
+pub fn spawn_with<D>(mut cpu: Computer, mut dev: D) -> JoinHandle<Result<Computer, ExecError>>
+    where D: Device + Send + 'static
+{
+    thread::spawn(move || {
+        cpu.run(&mut dev)?;
+        Ok(cpu)
+    })
+}
+
+pub fn spawn_computer(cpu: Computer, recv: Receiver<Word>, send: Sender<Word>)
+                      -> JoinHandle<Result<Computer, ExecError>>
+{
+    spawn_with(cpu, ChannelDev::new(recv, send))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exec::ExecFault;
+    use std::sync::mpsc::channel;
+
+    fn doubler() -> Computer {
+        // loop { out(in() * 2) }
+        Computer::new(vec![3,11,1002,11,2,11,4,11,1105,1,0])
+    }
+
+    #[test]
+    fn pipeline() {
+        let (s0, r0) = channel();
+        let (s1, r1) = channel();
+        let (s2, r2) = channel();
+        let j0 = spawn_computer(doubler(), r0, s1);
+        let j1 = spawn_computer(doubler(), r1, s2);
+        for i in 0..5 {
+            s0.send(i).unwrap();
+        }
+        let outs: Vec<_> = r2.iter().take(5).collect();
+        assert_eq!(outs, vec![0, 4, 8, 12, 16]);
+        drop(s0);
+        for j in [j0, j1] {
+            let err = j.join().unwrap().err().unwrap();
+            assert_eq!(err.fault, ExecFault::IO(IOError));
+            assert_eq!(err.pc, 0);
+        }
+    }
+
+    #[test]
+    fn halted_returns_cpu() {
+        let (s0, r0) = channel();
+        let (s1, r1) = channel();
+        let j = spawn_computer(Computer::new(vec![3,0,4,0,99]), r0, s1);
+        s0.send(17).unwrap();
+        assert_eq!(r1.recv(), Ok(17));
+        let cpu = j.join().unwrap().unwrap();
+        assert_eq!(cpu.read(0), Ok(17));
+    }
+
+    #[test]
+    fn peer_hangup_on_output() {
+        let (s0, r0) = channel();
+        let (s1, r1) = channel();
+        drop(r1);
+        let j = spawn_computer(doubler(), r0, s1);
+        s0.send(1).unwrap();
+        let err = j.join().unwrap().err().unwrap();
+        assert_eq!(err.fault, ExecFault::IO(IOError));
+        assert_eq!(err.pc, 6);
+    }
+
+    #[test]
+    fn input_timeout() {
+        let (_s0, r0) = channel();
+        let (s1, _r1) = channel();
+        let dev = ChannelDev::new(r0, s1).with_timeout(Duration::from_millis(10));
+        let err = spawn_with(doubler(), dev).join().unwrap().err().unwrap();
+        assert_eq!(err.fault, ExecFault::IO(IOError));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::Word;
+use crate::isa::Isa;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Insn {
@@ -25,6 +26,8 @@ pub enum Opcode {
     CmpEq,
     SetBase,
     Halt,
+    // Registered in an Isa; the length includes the opcode word.
+    Ext { code: Word, len: Word },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -79,17 +82,46 @@ impl Opcode {
             Opcode::Jnz | Opcode::Jz => 3,
             Opcode::In | Opcode::Out | Opcode::SetBase => 2,
             Opcode::Halt => 1,
+            Opcode::Ext { len, .. } => len,
+        }
+    }
+
+    pub fn decode_with(w: Word, isa: Option<&Isa>) -> Result<Self, OpcodeFault> {
+        Self::decode(w).or_else(|fault| {
+            isa.and_then(|isa| isa.get(w))
+                .map(|ext| Opcode::Ext { code: w, len: ext.size() })
+                .ok_or(fault)
+        })
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Mul => "mul",
+            Opcode::In => "in",
+            Opcode::Out => "out",
+            Opcode::Jnz => "jnz",
+            Opcode::Jz => "jz",
+            Opcode::CmpLt => "lt",
+            Opcode::CmpEq => "eq",
+            Opcode::SetBase => "arb",
+            Opcode::Halt => "halt",
+            Opcode::Ext { .. } => "ext",
         }
     }
 }
 
 impl Insn {
     pub fn decode(w: Word) -> Result<Self, DecodeFault> {
+        Self::decode_with(w, None)
+    }
+
+    pub fn decode_with(w: Word, isa: Option<&Isa>) -> Result<Self, DecodeFault> {
         if w < 0 {
             return Err(DecodeFault::Negative(w));
         }
         let mut a = w;
-        let opcode = Opcode::decode(a % 100).map_err(DecodeFault::Opcode)?;
+        let opcode = Opcode::decode_with(a % 100, isa).map_err(DecodeFault::Opcode)?;
         a /= 100;
         let mut modes = [None; 3];
         for i in 0..3 {
@@ -0,0 +1,457 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::Word;
+use crate::cfg::{Block, Cfg, CfgError, Exit};
+use crate::decode::{Insn, Mode, Opcode};
+
+// Renders memory as `m[addr]` and relative-mode slots as `fp[k]`; an
+// operand that the program patches at run time is shown as the memory
+// word it comes from.
+struct Render<'a> {
+    mem: &'a [Word],
+    cfg: &'a Cfg,
+}
+
+impl Render<'_> {
+    fn word(&self, addr: Word) -> Word {
+        self.mem.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    fn field(&self, addr: Word) -> String {
+        if self.cfg.written.contains(&addr) {
+            format!("m[{}]", addr)
+        } else {
+            self.word(addr).to_string()
+        }
+    }
+
+    fn operand(&self, insn: &Insn, addr: Word, idx: usize) -> String {
+        let field_addr = addr + 1 + idx as Word;
+        let field = self.field(field_addr);
+        match insn.modes[idx] {
+            Mode::Immediate => field,
+            Mode::Position => format!("m[{}]", field),
+            Mode::Relative if self.cfg.written.contains(&field_addr) => format!("m[fp + {}]", field),
+            Mode::Relative => format!("fp[{}]", field),
+        }
+    }
+
+    fn stmt(&self, addr: Word) -> Option<String> {
+        let insn = match Insn::decode(self.word(addr)) {
+            Ok(insn) => insn,
+            Err(_) => return Some(format!("invalid({});", self.word(addr))),
+        };
+        let op = |i| self.operand(&insn, addr, i);
+        Some(match insn.opcode {
+            Opcode::Add if op(1) == "0" => format!("{} = {};", op(2), op(0)),
+            Opcode::Add if op(0) == "0" => format!("{} = {};", op(2), op(1)),
+            Opcode::Add => format!("{} = {} + {};", op(2), op(0), op(1)),
+            Opcode::Mul => format!("{} = {} * {};", op(2), op(0), op(1)),
+            Opcode::CmpLt => format!("{} = {} < {};", op(2), op(0), op(1)),
+            Opcode::CmpEq => format!("{} = {} == {};", op(2), op(0), op(1)),
+            Opcode::In => format!("{} = input();", op(0)),
+            Opcode::Out => format!("output({});", op(0)),
+            Opcode::SetBase => match op(0).parse::<Word>() {
+                Ok(n) if n >= 0 => format!("push_frame({});", n),
+                Ok(n) => format!("pop_frame({});", -n),
+                Err(_) => format!("fp += {};", op(0)),
+            },
+            Opcode::Halt => "halt();".to_owned(),
+            Opcode::Jnz | Opcode::Jz | Opcode::Ext { .. } => return None,
+        })
+    }
+
+    fn cond(&self, addr: Word) -> String {
+        let insn = Insn::decode(self.word(addr)).unwrap();
+        let test = if insn.opcode == Opcode::Jnz { "!=" } else { "==" };
+        format!("{} {} 0", self.operand(&insn, addr, 0), test)
+    }
+
+    // A jump through a stack slot is a return.
+    fn is_return(&self, addr: Word) -> bool {
+        Insn::decode(self.word(addr)).is_ok_and(|insn| insn.modes[1] == Mode::Relative)
+    }
+
+    // The calling idiom: store the address after the jump in a stack
+    // slot, then jump to the function.
+    fn call_target(&self, block: &Block) -> Option<Word> {
+        let target = match block.exit {
+            Exit::Jump(target) => target,
+            _ => return None,
+        };
+        let &[.., store, _] = &block.insns[..] else { return None };
+        let insn = Insn::decode(self.word(store)).ok()?;
+        let ret = self.word(store + 1).checked_add(self.word(store + 2))?;
+        let ok = insn.opcode == Opcode::Add
+            && insn.modes == [Mode::Immediate, Mode::Immediate, Mode::Relative]
+            && ret == block.end
+            && !(store..block.end).any(|a| self.cfg.written.contains(&a));
+        if ok { Some(target) } else { None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Flow {
+    Next(Word),
+    Call(Word, Word),
+    Branch(String, Word, Word),
+    // A conditional return or computed jump, then the fallthrough.
+    Maybe(String, Word),
+    Stop,
+}
+
+impl Flow {
+    fn succs(&self) -> Vec<Word> {
+        match *self {
+            Flow::Next(next) | Flow::Call(_, next) | Flow::Maybe(_, next) => vec![next],
+            Flow::Branch(_, taken, fall) => vec![taken, fall],
+            Flow::Stop => vec![],
+        }
+    }
+}
+
+struct Node {
+    stmts: Vec<String>,
+    flow: Flow,
+}
+
+fn lower(r: &Render, block: &Block) -> Node {
+    let last = *block.insns.last().unwrap();
+    let mut stmts: Vec<_> = block.insns.iter().filter_map(|&a| r.stmt(a)).collect();
+    let call = r.call_target(block);
+    if call.is_some() {
+        stmts.pop();
+    }
+    let flow = match (block.exit, call) {
+        (Exit::Jump(target), Some(_)) => Flow::Call(target, block.end),
+        (Exit::Fall(next), _) | (Exit::Jump(next), _) => Flow::Next(next),
+        (Exit::Branch { taken, fall }, _) => Flow::Branch(r.cond(last), taken, fall),
+        (Exit::Indirect { fall }, _) => {
+            let insn = Insn::decode(r.word(last)).unwrap();
+            let jump = if r.is_return(last) {
+                "return;".to_owned()
+            } else {
+                format!("goto *{};", r.operand(&insn, last, 1))
+            };
+            match fall {
+                Some(fall) => Flow::Maybe(format!("if ({}) {}", r.cond(last), jump), fall),
+                None => {
+                    stmts.push(jump);
+                    Flow::Stop
+                }
+            }
+        }
+        (Exit::Halt, _) | (Exit::Invalid, _) => Flow::Stop,
+    };
+    Node { stmts, flow }
+}
+
+// Post-dominator sets as bit vectors, with one extra node for "exit".
+struct PostDom {
+    index: BTreeMap<Word, usize>,
+    addrs: Vec<Word>,
+    sets: Vec<Vec<u64>>,
+}
+
+impl PostDom {
+    fn new(nodes: &BTreeMap<Word, Node>, blocks: &BTreeSet<Word>) -> Self {
+        let addrs: Vec<_> = blocks.iter().copied().collect();
+        let index: BTreeMap<_, _> = addrs.iter().enumerate().map(|(i, &a)| (a, i)).collect();
+        let n = addrs.len() + 1;
+        let words = n.div_ceil(64);
+        let full = vec![!0u64; words];
+        let mut sets = vec![full; n];
+        sets[n - 1] = vec![0; words];
+        sets[n - 1][(n - 1) / 64] |= 1 << ((n - 1) % 64);
+        let succs: Vec<Vec<usize>> = addrs.iter().map(|a| {
+            let s: Vec<_> = nodes[a].flow.succs().iter().filter_map(|s| index.get(s).copied()).collect();
+            if s.is_empty() { vec![n - 1] } else { s }
+        }).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in (0..addrs.len()).rev() {
+                let mut new = sets[succs[i][0]].clone();
+                for &s in &succs[i][1..] {
+                    for (w, x) in new.iter_mut().zip(&sets[s]) {
+                        *w &= x;
+                    }
+                }
+                new[i / 64] |= 1 << (i % 64);
+                if new != sets[i] {
+                    sets[i] = new;
+                    changed = true;
+                }
+            }
+        }
+        Self { index, addrs, sets }
+    }
+
+    // Strict post-dominators of `addr`, nearest first.
+    fn chain(&self, addr: Word) -> Vec<Word> {
+        let i = self.index[&addr];
+        let count = |j: usize| self.sets[j].iter().map(|w| w.count_ones()).sum::<u32>();
+        let mut doms: Vec<_> = (0..self.addrs.len())
+            .filter(|&j| j != i && self.sets[i][j / 64] & (1 << (j % 64)) != 0)
+            .collect();
+        doms.sort_by_key(|&j| std::cmp::Reverse(count(j)));
+        doms.into_iter().map(|j| self.addrs[j]).collect()
+    }
+}
+
+fn negate(cond: &str) -> String {
+    match cond.strip_suffix(" == 0") {
+        Some(x) => format!("{} != 0", x),
+        None => format!("{} == 0", cond.strip_suffix(" != 0").unwrap()),
+    }
+}
+
+struct Loop {
+    header: Word,
+    exit: Option<Word>,
+}
+
+struct Func<'a> {
+    nodes: &'a BTreeMap<Word, Node>,
+    pdom: PostDom,
+    loops: BTreeMap<Word, Option<Word>>,
+    emitted: BTreeMap<Word, usize>,
+    gotos: BTreeSet<Word>,
+    ctx: Vec<Loop>,
+    lines: Vec<(usize, String)>,
+}
+
+impl Func<'_> {
+    fn line(&mut self, depth: usize, s: String) {
+        self.lines.push((depth, s));
+    }
+
+    fn seq(&mut self, mut cur: Word, stop: Option<Word>, depth: usize) {
+        loop {
+            if Some(cur) == stop {
+                return;
+            }
+            if let Some(l) = self.ctx.last() {
+                if cur == l.header && self.emitted.contains_key(&cur) {
+                    return self.line(depth, "continue;".to_owned());
+                }
+                if Some(cur) == l.exit {
+                    return self.line(depth, "break;".to_owned());
+                }
+            }
+            if self.emitted.contains_key(&cur) || !self.nodes.contains_key(&cur) {
+                self.gotos.insert(cur);
+                return self.line(depth, format!("goto L{};", cur));
+            }
+            if let Some(&exit) = self.loops.get(&cur) {
+                if !self.ctx.iter().any(|l| l.header == cur) {
+                    self.line(depth, "loop {".to_owned());
+                    self.ctx.push(Loop { header: cur, exit });
+                    self.seq(cur, None, depth + 1);
+                    self.ctx.pop();
+                    self.line(depth, "}".to_owned());
+                    match exit {
+                        Some(exit) => { cur = exit; continue }
+                        None => return,
+                    }
+                }
+            }
+            self.emitted.insert(cur, self.lines.len());
+            let node = &self.nodes[&cur];
+            for s in node.stmts.clone() {
+                self.line(depth, s);
+            }
+            match node.flow.clone() {
+                Flow::Next(next) => cur = next,
+                Flow::Call(target, ret) => {
+                    self.line(depth, format!("f{}();", target));
+                    cur = ret;
+                }
+                Flow::Maybe(s, next) => {
+                    self.line(depth, s);
+                    cur = next;
+                }
+                Flow::Branch(cond, taken, fall) => {
+                    let join = self.pdom.chain(cur).first().copied();
+                    if join == Some(fall) {
+                        self.line(depth, format!("if ({}) {{", cond));
+                        self.seq(taken, join, depth + 1);
+                    } else if join == Some(taken) {
+                        self.line(depth, format!("if ({}) {{", negate(&cond)));
+                        self.seq(fall, join, depth + 1);
+                    } else {
+                        self.line(depth, format!("if ({}) {{", cond));
+                        self.seq(taken, join, depth + 1);
+                        self.line(depth, "} else {".to_owned());
+                        self.seq(fall, join, depth + 1);
+                    }
+                    self.line(depth, "}".to_owned());
+                    match join {
+                        Some(join) => cur = join,
+                        None => return,
+                    }
+                }
+                Flow::Stop => return,
+            }
+        }
+    }
+}
+
+// Natural loops: headers are targets of back edges in a depth-first
+// search, and each loop is left through the header's nearest
+// post-dominator outside of it.
+fn find_loops(nodes: &BTreeMap<Word, Node>, blocks: &BTreeSet<Word>, entry: Word, pdom: &PostDom)
+              -> BTreeMap<Word, Option<Word>>
+{
+    let succs = |a: Word| -> Vec<Word> {
+        nodes[&a].flow.succs().into_iter().filter(|s| blocks.contains(s)).collect()
+    };
+    let mut back: BTreeMap<Word, Vec<Word>> = BTreeMap::new();
+    let mut state: BTreeMap<Word, bool> = BTreeMap::new(); // true while on the stack
+    let mut stack = vec![(entry, 0)];
+    state.insert(entry, true);
+    while let Some(&mut (a, ref mut i)) = stack.last_mut() {
+        let ss = succs(a);
+        if *i < ss.len() {
+            let s = ss[*i];
+            *i += 1;
+            match state.get(&s) {
+                Some(true) => back.entry(s).or_default().push(a),
+                Some(false) => (),
+                None => {
+                    state.insert(s, true);
+                    stack.push((s, 0));
+                }
+            }
+        } else {
+            state.insert(a, false);
+            stack.pop();
+        }
+    }
+    let mut preds: BTreeMap<Word, Vec<Word>> = BTreeMap::new();
+    for &a in blocks {
+        for s in succs(a) {
+            preds.entry(s).or_default().push(a);
+        }
+    }
+    back.into_iter().map(|(header, tails)| {
+        let mut body: BTreeSet<_> = Some(header).into_iter().collect();
+        let mut todo = tails;
+        while let Some(a) = todo.pop() {
+            if body.insert(a) {
+                todo.extend(preds.get(&a).into_iter().flatten());
+            }
+        }
+        let exit = pdom.chain(header).into_iter().find(|a| !body.contains(a));
+        (header, exit)
+    }).collect()
+}
+
+pub fn decompile(mem: &[Word]) -> Result<String, CfgError> {
+    let cfg = Cfg::build(mem)?;
+    let r = Render { mem, cfg: &cfg };
+    let nodes: BTreeMap<_, _> = cfg.blocks.values().map(|b| (b.start, lower(&r, b))).collect();
+    let mut entries: BTreeSet<_> = Some(0).into_iter().collect();
+    entries.extend(nodes.values().filter_map(|n| match n.flow {
+        Flow::Call(target, _) => Some(target),
+        _ => None,
+    }));
+
+    let mut out = String::new();
+    for &entry in entries.iter().filter(|e| nodes.contains_key(e)) {
+        let mut blocks = BTreeSet::new();
+        let mut todo = vec![entry];
+        while let Some(a) = todo.pop() {
+            if nodes.contains_key(&a) && blocks.insert(a) {
+                todo.extend(nodes[&a].flow.succs());
+            }
+        }
+        let pdom = PostDom::new(&nodes, &blocks);
+        let loops = find_loops(&nodes, &blocks, entry, &pdom);
+        let mut f = Func {
+            nodes: &nodes, pdom, loops,
+            emitted: BTreeMap::new(), gotos: BTreeSet::new(), ctx: vec![], lines: vec![],
+        };
+        f.seq(entry, None, 1);
+        // Anything left over was only reachable by goto.
+        while let Some(&a) = blocks.iter().find(|a| !f.emitted.contains_key(a)) {
+            f.gotos.insert(a);
+            f.seq(a, None, 1);
+        }
+        let labels: BTreeMap<usize, Word> = f.gotos.iter()
+            .filter_map(|a| f.emitted.get(a).map(|&line| (line, *a)))
+            .collect();
+        out += &format!("void f{}() {{\n", entry);
+        for (i, (depth, s)) in f.lines.iter().enumerate() {
+            if let Some(a) = labels.get(&i) {
+                out += &format!("L{}:\n", a);
+            }
+            out += &format!("{:1$}{2}\n", "", depth * 4, s);
+        }
+        out += "}\n\n";
+    }
+    out.pop();
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn if_else() {
+        // Day 5's "compare to 8" example.
+        let mem = [3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+                   1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+                   999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99];
+        assert_eq!(decompile(&mem).unwrap(), "\
+void f0() {
+    m[21] = input();
+    m[20] = m[21] == 8;
+    if (m[20] != 0) {
+        m[20] = m[21] * 125;
+        output(m[20]);
+    } else {
+        m[20] = 8 < m[21];
+        if (m[20] == 0) {
+            output(999);
+        } else {
+            m[20] = 1000 + 1;
+            output(m[20]);
+        }
+    }
+    halt();
+}
+");
+    }
+
+    #[test]
+    fn loops_and_calls() {
+        // Counts m[40] down from the input, calling a function that
+        // outputs it each time.
+        let mem = [109,1,3,40,1006,40,21,21101,14,0,0,1105,1,22,1001,40,-1,40,1105,1,4,
+                   99,109,3,4,40,109,-3,2106,0,0];
+        assert_eq!(decompile(&mem).unwrap(), "\
+void f0() {
+    push_frame(1);
+    m[40] = input();
+    loop {
+        if (m[40] != 0) {
+            f22();
+            m[40] = m[40] + -1;
+            continue;
+        }
+        break;
+    }
+    halt();
+}
+
+void f22() {
+    push_frame(3);
+    output(m[40]);
+    pop_frame(3);
+    return;
+}
+");
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+
+use crate::Word;
+use crate::decode::{Insn, Mode, Opcode};
+use crate::isa::Isa;
+
+// Operands are written `[n]` for position mode, `[rb+n]` for relative
+// mode, and bare for immediate mode.  Words that don't decode are shown
+// as `.word n`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub addr: Word,
+    pub len: Word,
+    pub text: String,
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:6}  {}", self.addr, self.text)
+    }
+}
+
+pub fn operand(mode: Mode, field: Word) -> String {
+    match mode {
+        Mode::Position => format!("[{}]", field),
+        Mode::Immediate => format!("{}", field),
+        Mode::Relative if field < 0 => format!("[rb{}]", field),
+        Mode::Relative => format!("[rb+{}]", field),
+    }
+}
+
+fn mnemonic(opcode: Opcode, isa: Option<&Isa>) -> &'static str {
+    match opcode {
+        Opcode::Ext { code, .. } => isa.and_then(|isa| isa.get(code)).map_or("ext", |ext| ext.name()),
+        _ => opcode.mnemonic(),
+    }
+}
+
+// Memory past the end reads as zero, as it does for a Computer.
+pub fn disasm_at(mem: &[Word], addr: Word, isa: Option<&Isa>) -> Line {
+    let word = |a: Word| mem.get(a as usize).copied().unwrap_or(0);
+    let w = word(addr);
+    match Insn::decode_with(w, isa) {
+        Ok(insn) => {
+            let len = insn.opcode.len();
+            let args: Vec<_> = (1..len)
+                .map(|i| operand(insn.modes[i as usize - 1], word(addr + i)))
+                .collect();
+            let name = mnemonic(insn.opcode, isa);
+            let text = if args.is_empty() { name.to_owned() } else { format!("{} {}", name, args.join(", ")) };
+            Line { addr, len, text }
+        }
+        Err(_) => Line { addr, len: 1, text: format!(".word {}", w) },
+    }
+}
+
+// A linear sweep, so data mixed in with code will throw it off.
+pub fn disasm(mem: &[Word], isa: Option<&Isa>) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut addr = 0;
+    while (addr as usize) < mem.len() {
+        let line = disasm_at(mem, addr, isa);
+        addr += line.len;
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::isa::ExtInsn;
+
+    fn texts(lines: &[Line]) -> Vec<&str> {
+        lines.iter().map(|l| l.text.as_str()).collect()
+    }
+
+    #[test]
+    fn basic() {
+        let lines = disasm(&[3,11,1001,11,1,11,204,-3,1105,1,0,77], None);
+        assert_eq!(texts(&lines), vec!["in [11]", "add [11], 1, [11]", "out [rb-3]",
+                                       "jnz 1, 0", ".word 77"]);
+        assert_eq!(lines.iter().map(|l| l.addr).collect::<Vec<_>>(), vec![0, 2, 6, 8, 11]);
+        assert_eq!(lines[1].to_string(), "     2  add [11], 1, [11]");
+        assert_eq!(disasm_at(&[1], 0, None).text, "add [0], [0], [0]");
+    }
+
+    #[test]
+    fn extended() {
+        let mut isa = Isa::new();
+        isa.register(10, ExtInsn::div()).unwrap();
+        isa.register(12, ExtInsn::debug()).unwrap();
+        let prog = [1010,100,7,101,212,5,99];
+        assert_eq!(texts(&disasm(&prog, None)), vec![".word 1010", ".word 100", "lt [101], [212], [5]", "halt"]);
+        assert_eq!(texts(&disasm(&prog, Some(&isa))), vec!["div [100], 7, [101]", "dbg [rb+5]", "halt"]);
+    }
+}
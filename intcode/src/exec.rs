@@ -1,6 +1,8 @@
 use std::convert::From;
+use std::sync::Arc;
 
 use crate::{Word, ParseError, decode::{Insn, Mode, Opcode, DecodeFault}};
+use crate::isa::{ExtCtx, Isa};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MemMode {
@@ -29,6 +31,8 @@ pub enum ExecFault {
     WriteImmediate,
     IO(IOError),
     Overflow(ArithOp, Word, Word),
+    // An extension instruction's own failure.
+    Ext(Word, &'static str),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -61,8 +65,24 @@ pub struct Computer {
     pc: Word,
     base: Word,
     mem: Vec<Word>,
+    isa: Option<Arc<Isa>>,
+    trap: Option<Arc<TrapHandler>>,
 }
 
+// What to do after a trap: Skip moves past the faulting instruction (one
+// word, if it didn't decode), Jump goes elsewhere, Retry executes the
+// same pc again (after the handler has patched things up), and Abort
+// returns the error as if there were no handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapAction {
+    Skip,
+    Jump(Word),
+    Retry,
+    Abort,
+}
+
+pub type TrapHandler = dyn Fn(&ExecError, &mut Computer) -> TrapAction + Send + Sync;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Stepped {
     Ok,
@@ -97,13 +117,71 @@ fn alu_mul(x: Word, y: Word) -> Result<Word, ExecFault> {
 impl Computer {
     pub fn new(mem: Vec<Word>) -> Self {
         assert!(mem.len() - 1 <= Word::max_value() as usize);
-        Self { pc: 0, base: 0, mem }
+        Self { pc: 0, base: 0, mem, isa: None, trap: None }
+    }
+
+    pub fn with_isa(self, isa: Arc<Isa>) -> Self {
+        Self { isa: Some(isa), ..self }
+    }
+
+    pub fn isa(&self) -> Option<&Isa> {
+        self.isa.as_deref()
+    }
+
+    // The instruction at the pc, if it might take input: an In, or an
+    // extension that declares I/O.
+    pub(crate) fn input_insn(&self) -> Option<Insn> {
+        let insn = Insn::decode_with(self.read(self.pc).ok()?, self.isa()).ok()?;
+        let takes_input = match insn.opcode {
+            Opcode::In => true,
+            Opcode::Ext { code, .. } =>
+                self.isa().and_then(|isa| isa.get(code)).is_some_and(|ext| ext.does_io()),
+            _ => false,
+        };
+        takes_input.then_some(insn)
+    }
+
+    // I/O errors never trap, because that's how a device says it has
+    // nothing to read yet.
+    pub fn with_trap<F>(self, handler: F) -> Self
+        where F: Fn(&ExecError, &mut Computer) -> TrapAction + Send + Sync + 'static
+    {
+        Self { trap: Some(Arc::new(handler)), ..self }
     }
 
     pub fn from_str(s: &str) -> Result<Self, ParseError> {
         Ok(Self::new(crate::parse(s)?))
     }
 
+    pub fn with_state(pc: Word, base: Word, mem: Vec<Word>) -> Self {
+        Self { pc, base, ..Self::new(mem) }
+    }
+
+    pub fn pc(&self) -> Word {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: Word) {
+        self.pc = pc;
+    }
+
+    pub fn base(&self) -> Word {
+        self.base
+    }
+
+    pub fn set_base(&mut self, base: Word) {
+        self.base = base;
+    }
+
+    // Memory beyond this reads as zero.
+    pub fn mem_len(&self) -> usize {
+        self.mem.len()
+    }
+
+    pub fn mem(&self) -> &[Word] {
+        &self.mem
+    }
+
     fn xread(&self, addr: Word, mode: MemMode) -> Result<Word, MemFault> {
         if addr < 0 {
             return Err(MemFault{ addr, mode });
@@ -136,7 +214,7 @@ impl Computer {
         self.base.checked_add(field).ok_or(ExecFault::Overflow(ArithOp::Lea, self.base, field))
     }
 
-    fn read_param(&self, insn: &Insn, idx: usize) -> Result<Word, ExecFault> {
+    pub(crate) fn read_param(&self, insn: &Insn, idx: usize) -> Result<Word, ExecFault> {
         let field = self.iread(1 + idx as Word)?;
         match insn.modes[idx] {
             Mode::Immediate => Ok(field),
@@ -145,7 +223,7 @@ impl Computer {
         }
     }
 
-    fn write_param(&mut self, insn: &Insn, idx: usize, val: Word) -> Result<(), ExecFault> {
+    pub(crate) fn write_param(&mut self, insn: &Insn, idx: usize, val: Word) -> Result<(), ExecFault> {
         let field = self.iread(1 + idx as Word)?;
         match insn.modes[idx] {
             Mode::Immediate => Err(ExecFault::WriteImmediate),
@@ -155,7 +233,7 @@ impl Computer {
     }
 
     fn exec(&mut self, io: &mut dyn Device) -> Result<Stepped, ExecFault> {
-        let insn = Insn::decode(self.iread(0)?)?;
+        let insn = Insn::decode_with(self.iread(0)?, self.isa())?;
         let mut npc = self.pc + insn.opcode.len();
         match insn.opcode {
             Opcode::Add =>
@@ -187,9 +265,20 @@ impl Computer {
                                  setcc(self.read_param(&insn, 0)? ==
                                        self.read_param(&insn, 1)?)),
             Opcode::SetBase =>
-                Ok(self.base += self.read_param(&insn, 0)?),
+                Ok(self.base = self.lea_rel(self.read_param(&insn, 0)?)?),
             Opcode::Halt =>
                 return Ok(Stepped::Halted),
+            Opcode::Ext { code, .. } => {
+                let isa = self.isa.clone().expect("extension decoded without an ISA");
+                let ext = isa.get(code).expect("extension went missing");
+                let mut ctx = ExtCtx::new(self, &insn, ext, io, npc);
+                ext.exec(&mut ctx)?;
+                if ctx.halted() {
+                    return Ok(Stepped::Halted);
+                }
+                npc = ctx.npc();
+                Ok(())
+            }
         }?;
         self.pc = npc;
         return Ok(Stepped::Ok);
@@ -197,7 +286,26 @@ impl Computer {
 
     pub fn step(&mut self, io: &mut dyn Device) -> Result<Stepped, ExecError> {
         let pc = self.pc;
-        self.exec(io).map_err(|fault| ExecError { pc, fault })
+        let err = match self.exec(io) {
+            Ok(stepped) => return Ok(stepped),
+            Err(fault) => ExecError { pc, fault },
+        };
+        let trap = match (&self.trap, &err.fault) {
+            (_, ExecFault::IO(_)) | (None, _) => return Err(err),
+            (Some(trap), _) => trap.clone(),
+        };
+        match trap(&err, self) {
+            TrapAction::Skip => {
+                let len = self.read(pc).ok()
+                    .and_then(|w| Insn::decode_with(w, self.isa()).ok())
+                    .map_or(1, |insn| insn.opcode.len());
+                self.pc = pc + len;
+            }
+            TrapAction::Jump(target) => self.pc = target,
+            TrapAction::Retry => (),
+            TrapAction::Abort => return Err(err),
+        }
+        Ok(Stepped::Ok)
     }
 
     pub fn run(&mut self, io: &mut dyn Device) -> Result<(), ExecError> {
@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::{Computer, Device, ExecError, Stepped, Word};
+use crate::decode::{Insn, Mode, Opcode};
+use crate::exec::MemMode;
+use crate::isa::Operand;
+
+pub const ROW: usize = 64;
+
+// Counts of instruction fetches, data reads and data writes per
+// address, worked out from each instruction before it's executed.  A
+// program can read anywhere without allocating, so this is sparse.
+#[derive(Debug, Clone, Default)]
+pub struct Heatmap {
+    counts: BTreeMap<usize, [u64; 3]>,
+}
+
+fn slot(mode: MemMode) -> usize {
+    match mode {
+        MemMode::IRead => 0,
+        MemMode::DRead => 1,
+        MemMode::DWrite => 2,
+    }
+}
+
+// Which operands an instruction reads and writes; a jump only fetches
+// its target if it's taken.
+fn operands(cpu: &Computer, insn: &Insn) -> Vec<(usize, MemMode)> {
+    use MemMode::{DRead, DWrite};
+    match insn.opcode {
+        Opcode::Add | Opcode::Mul | Opcode::CmpLt | Opcode::CmpEq => vec![(0, DRead), (1, DRead), (2, DWrite)],
+        Opcode::In => vec![(0, DWrite)],
+        Opcode::Out | Opcode::SetBase => vec![(0, DRead)],
+        Opcode::Jnz | Opcode::Jz => {
+            let cond = address(cpu, insn, 0).map_or_else(
+                || cpu.read(cpu.pc() + 1).unwrap_or(0),
+                |addr| cpu.read(addr).unwrap_or(0));
+            if (cond != 0) == (insn.opcode == Opcode::Jnz) {
+                vec![(0, DRead), (1, DRead)]
+            } else {
+                vec![(0, DRead)]
+            }
+        }
+        Opcode::Halt => vec![],
+        Opcode::Ext { code, .. } => cpu.isa().and_then(|isa| isa.get(code)).map_or(vec![], |ext| {
+            ext.operands().iter().enumerate().map(|(i, &op)| match op {
+                Operand::Read => (i, DRead),
+                Operand::Write => (i, DWrite),
+            }).collect()
+        }),
+    }
+}
+
+// The memory address an operand refers to, if it isn't immediate.
+fn address(cpu: &Computer, insn: &Insn, idx: usize) -> Option<Word> {
+    let field = cpu.read(cpu.pc() + 1 + idx as Word).ok()?;
+    match insn.modes[idx] {
+        Mode::Immediate => None,
+        Mode::Position => Some(field),
+        Mode::Relative => cpu.base().checked_add(field),
+    }
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, addr: Word, mode: MemMode) {
+        if addr < 0 {
+            return;
+        }
+        self.counts.entry(addr as usize).or_insert([0; 3])[slot(mode)] += 1;
+    }
+
+    pub fn count(&self, addr: Word, mode: MemMode) -> u64 {
+        self.counts.get(&(addr as usize)).map_or(0, |c| c[slot(mode)])
+    }
+
+    // One past the highest address touched.
+    pub fn len(&self) -> usize {
+        self.counts.keys().next_back().map_or(0, |&addr| addr + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    // Faulting instructions aren't counted.
+    pub fn step(&mut self, cpu: &mut Computer, io: &mut dyn Device) -> Result<Stepped, ExecError> {
+        let pc = cpu.pc();
+        let insn = cpu.read(pc).ok().and_then(|w| Insn::decode_with(w, cpu.isa()).ok());
+        let accesses: Vec<_> = insn.iter().flat_map(|insn| {
+            let data = operands(cpu, insn).into_iter()
+                .filter_map(|(i, mode)| address(cpu, insn, i).map(|a| (a, mode)));
+            (pc..pc + insn.opcode.len()).map(|a| (a, MemMode::IRead)).chain(data).collect::<Vec<_>>()
+        }).collect();
+        let stepped = cpu.step(io)?;
+        for (addr, mode) in accesses {
+            self.record(addr, mode);
+        }
+        Ok(stepped)
+    }
+
+    pub fn run(&mut self, cpu: &mut Computer, io: &mut dyn Device) -> Result<(), ExecError> {
+        while self.step(cpu, io)? == Stepped::Ok { }
+        Ok(())
+    }
+
+    // Green for instruction fetches, blue for reads and red for writes,
+    // each on a log scale relative to the busiest address.
+    pub fn color(&self, addr: usize) -> [u8; 3] {
+        self.color_with(addr, &self.maxes())
+    }
+
+    fn color_with(&self, addr: usize, max: &[u64; 3]) -> [u8; 3] {
+        let c = self.counts.get(&addr).copied().unwrap_or([0; 3]);
+        let level = |i: usize| -> u8 {
+            if c[i] == 0 {
+                0
+            } else {
+                (64.0 + 191.0 * (1.0 + c[i] as f64).ln() / (1.0 + max[i] as f64).ln()) as u8
+            }
+        };
+        [level(2), level(0), level(1)]
+    }
+
+    fn maxes(&self) -> [u64; 3] {
+        let mut max = [0; 3];
+        for c in self.counts.values() {
+            for i in 0..3 {
+                max[i] = max[i].max(c[i]);
+            }
+        }
+        max
+    }
+
+    // The starts of the rows with anything in them, in order.
+    fn rows(&self) -> Vec<usize> {
+        let mut rows: Vec<usize> = self.counts.keys().map(|&addr| addr - addr % ROW).collect();
+        rows.dedup();
+        rows
+    }
+
+    // One character per word, with runs of untouched rows elided.
+    pub fn write_ansi<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let max = self.maxes();
+        let len = self.len();
+        let mut next = 0;
+        for start in self.rows() {
+            if start != next {
+                writeln!(out, "{:>8}", "...")?;
+            }
+            next = start + ROW;
+            write!(out, "{:8} ", start)?;
+            for addr in start..next.min(len) {
+                let [r, g, b] = self.color_with(addr, &max);
+                write!(out, "\x1b[48;2;{};{};{}m ", r, g, b)?;
+            }
+            writeln!(out, "\x1b[0m")?;
+        }
+        Ok(())
+    }
+
+    // A binary PPM, `scale` pixels square per word.  Untouched rows are
+    // left out, as they could be most of the address space.
+    pub fn write_ppm<W: Write>(&self, mut out: W, scale: usize) -> io::Result<()> {
+        let max = self.maxes();
+        let mut rows = self.rows();
+        if rows.is_empty() {
+            rows.push(0);
+        }
+        write!(out, "P6\n{} {}\n255\n", ROW * scale, rows.len() * scale)?;
+        for start in rows {
+            let mut line = Vec::with_capacity(ROW * scale * 3);
+            for addr in start..start + ROW {
+                let rgb = self.color_with(addr, &max);
+                for _ in 0..scale {
+                    line.extend_from_slice(&rgb);
+                }
+            }
+            for _ in 0..scale {
+                out.write_all(&line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sched::TapeDev;
+
+    // Day 9's quine, which reads all of itself through the stack pointer.
+    const QUINE: &[Word] = &[109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+
+    #[test]
+    fn counts() {
+        let mut map = Heatmap::new();
+        let mut cpu = Computer::new(QUINE.to_vec());
+        let mut dev = TapeDev::new(&[]);
+        map.run(&mut cpu, &mut dev).unwrap();
+        assert_eq!(dev.output, QUINE.to_vec());
+        assert_eq!(map.count(0, MemMode::IRead), 16);
+        assert_eq!(map.count(2, MemMode::IRead), 16);
+        assert_eq!(map.count(15, MemMode::IRead), 1);
+        for addr in 0..16 {
+            assert_eq!(map.count(addr, MemMode::DRead), 1, "addr {}", addr);
+        }
+        assert_eq!(map.count(100, MemMode::DWrite), 16);
+        assert_eq!(map.count(100, MemMode::DRead), 32);
+        assert_eq!(map.count(101, MemMode::DWrite), 16);
+        assert_eq!(map.count(101, MemMode::DRead), 16);
+        assert_eq!(map.len(), 102);
+        assert_eq!(map.color(2), [0, 255, map.color(2)[2]]);
+        assert_eq!(map.color(50), [0, 0, 0]);
+    }
+
+    #[test]
+    fn jumps() {
+        // The target of the untaken jump isn't read.
+        let mut map = Heatmap::new();
+        let mut cpu = Computer::new(vec![6,7,8,5,7,9,99,1,99,10,99]);
+        map.run(&mut cpu, &mut ()).unwrap();
+        assert_eq!(map.count(8, MemMode::DRead), 0);
+        assert_eq!(map.count(9, MemMode::DRead), 1);
+        assert_eq!(cpu.pc(), 10);
+    }
+
+    #[test]
+    fn output() {
+        let mut map = Heatmap::new();
+        let mut cpu = Computer::new(QUINE.to_vec());
+        map.run(&mut cpu, &mut TapeDev::new(&[])).unwrap();
+
+        let mut ppm = vec![];
+        map.write_ppm(&mut ppm, 2).unwrap();
+        let header = b"P6\n128 4\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 128 * 4 * 3);
+        assert_eq!(&ppm[header.len() + 6..header.len() + 9], &map.color(1));
+
+        let mut ansi = vec![];
+        map.write_ansi(&mut ansi).unwrap();
+        let ansi = String::from_utf8(ansi).unwrap();
+        let lines: Vec<_> = ansi.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("       0 \x1b[48;2;"));
+        assert!(lines[1].starts_with("      64 "));
+        assert_eq!(lines[1].matches("\x1b[48").count(), 102 - 64);
+    }
+
+    #[test]
+    fn far_read() {
+        // out(m[2^40]), which the CPU reads as 0 without allocating.
+        let far = 1 << 40;
+        let mut map = Heatmap::new();
+        let mut cpu = Computer::new(vec![4,far,99]);
+        let mut dev = TapeDev::new(&[]);
+        map.run(&mut cpu, &mut dev).unwrap();
+        assert_eq!(dev.output, vec![0]);
+        assert_eq!(map.count(far, MemMode::DRead), 1);
+        assert_eq!(map.len(), far as usize + 1);
+
+        let mut ansi = vec![];
+        map.write_ansi(&mut ansi).unwrap();
+        let ansi = String::from_utf8(ansi).unwrap();
+        let lines: Vec<_> = ansi.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "     ...");
+        assert!(lines[2].starts_with(&format!("{:8} ", far)));
+        assert_eq!(lines[2].matches("\x1b[48").count(), 1);
+
+        let mut ppm = vec![];
+        map.write_ppm(&mut ppm, 1).unwrap();
+        let header = b"P6\n64 2\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 64 * 2 * 3);
+    }
+}
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+
+use crate::{Computer, Device, Word};
+use crate::decode::{Insn, Opcode};
+use crate::exec::ExecFault;
+
+// What an extension instruction does with each of its operands; the
+// mode digits work as for the built-in instructions, so a Write operand
+// in immediate mode faults when executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Read,
+    Write,
+}
+
+type ExtFn = dyn Fn(&mut ExtCtx) -> Result<(), ExecFault> + Send + Sync;
+
+pub struct ExtInsn {
+    name: &'static str,
+    operands: Vec<Operand>,
+    io: bool,
+    exec: Box<ExtFn>,
+}
+
+impl ExtInsn {
+    pub fn new<F>(name: &'static str, operands: &[Operand], exec: F) -> Self
+        where F: Fn(&mut ExtCtx) -> Result<(), ExecFault> + Send + Sync + 'static
+    {
+        // There are only three mode digits.
+        assert!(operands.len() <= 3, "too many operands for {}", name);
+        Self { name, operands: operands.to_vec(), io: false, exec: Box::new(exec) }
+    }
+
+    // Lets the handler use the Computer's Device.  Undeclared I/O
+    // faults, so that schedulers can tell which instructions might wait.
+    pub fn with_io(mut self) -> Self {
+        self.io = true;
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn operands(&self) -> &[Operand] {
+        &self.operands
+    }
+
+    pub fn does_io(&self) -> bool {
+        self.io
+    }
+
+    pub fn size(&self) -> Word {
+        1 + self.operands.len() as Word
+    }
+
+    pub(crate) fn exec(&self, ctx: &mut ExtCtx) -> Result<(), ExecFault> {
+        (self.exec)(ctx)
+    }
+
+    // div a, b -> c; rounds toward zero.
+    pub fn div() -> Self {
+        Self::new("div", &[Operand::Read, Operand::Read, Operand::Write], |ctx| {
+            let (x, y) = (ctx.read(0)?, ctx.read(1)?);
+            let q = ctx.arith(x.checked_div(y), y)?;
+            ctx.write(2, q)
+        })
+    }
+
+    // mod a, b -> c; takes the sign of a, like Rust's %.
+    pub fn rem() -> Self {
+        Self::new("mod", &[Operand::Read, Operand::Read, Operand::Write], |ctx| {
+            let (x, y) = (ctx.read(0)?, ctx.read(1)?);
+            let r = ctx.arith(x.checked_rem(y), y)?;
+            ctx.write(2, r)
+        })
+    }
+
+    // dbg a; prints a and the pc to stderr.
+    pub fn debug() -> Self {
+        Self::new("dbg", &[Operand::Read], |ctx| {
+            eprintln!("intcode@{}: {}", ctx.pc(), ctx.read(0)?);
+            Ok(())
+        })
+    }
+
+    // trap n; hands the number and the machine to `handler`.
+    pub fn trap<F>(handler: F) -> Self
+        where F: Fn(Word, &mut ExtCtx) -> Result<(), ExecFault> + Send + Sync + 'static
+    {
+        Self::new("trap", &[Operand::Read], move |ctx| {
+            let num = ctx.read(0)?;
+            handler(num, ctx)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IsaError {
+    OutOfRange(Word),
+    BuiltIn(Word),
+    Taken(Word),
+}
+
+// Extra opcodes for a Computer, looked up only when the built-in
+// decoding fails, so they can't change the meaning of existing programs.
+#[derive(Default)]
+pub struct Isa {
+    ext: HashMap<Word, ExtInsn>,
+}
+
+impl Isa {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, code: Word, insn: ExtInsn) -> Result<(), IsaError> {
+        if !(0..100).contains(&code) {
+            return Err(IsaError::OutOfRange(code));
+        }
+        if Opcode::decode(code).is_ok() {
+            return Err(IsaError::BuiltIn(code));
+        }
+        if self.ext.contains_key(&code) {
+            return Err(IsaError::Taken(code));
+        }
+        self.ext.insert(code, insn);
+        Ok(())
+    }
+
+    pub fn get(&self, code: Word) -> Option<&ExtInsn> {
+        self.ext.get(&code)
+    }
+
+    pub fn codes(&self) -> Vec<Word> {
+        let mut codes: Vec<_> = self.ext.keys().copied().collect();
+        codes.sort();
+        codes
+    }
+}
+
+// The view of the machine an extension gets while it executes.  The pc
+// advances past the instruction afterwards unless it jumps or halts.
+pub struct ExtCtx<'a> {
+    cpu: &'a mut Computer,
+    insn: &'a Insn,
+    ext: &'a ExtInsn,
+    io: &'a mut dyn Device,
+    npc: Word,
+    halted: bool,
+}
+
+impl<'a> ExtCtx<'a> {
+    pub(crate) fn new(cpu: &'a mut Computer, insn: &'a Insn, ext: &'a ExtInsn,
+                      io: &'a mut dyn Device, npc: Word) -> Self {
+        Self { cpu, insn, ext, io, npc, halted: false }
+    }
+
+    pub(crate) fn npc(&self) -> Word {
+        self.npc
+    }
+
+    pub(crate) fn halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn code(&self) -> Word {
+        match self.insn.opcode {
+            Opcode::Ext { code, .. } => code,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn pc(&self) -> Word {
+        self.cpu.pc()
+    }
+
+    pub fn read(&self, idx: usize) -> Result<Word, ExecFault> {
+        self.check_operand(idx, Operand::Read)?;
+        self.cpu.read_param(self.insn, idx)
+    }
+
+    pub fn write(&mut self, idx: usize, val: Word) -> Result<(), ExecFault> {
+        self.check_operand(idx, Operand::Write)?;
+        self.cpu.write_param(self.insn, idx, val)
+    }
+
+    pub fn input(&mut self) -> Result<Word, ExecFault> {
+        self.check_io()?;
+        Ok(self.io.input()?)
+    }
+
+    pub fn output(&mut self, val: Word) -> Result<(), ExecFault> {
+        self.check_io()?;
+        Ok(self.io.output(val)?)
+    }
+
+    pub fn jump(&mut self, target: Word) {
+        self.npc = target;
+    }
+
+    pub fn halt(&mut self) {
+        self.halted = true;
+    }
+
+    pub fn computer(&mut self) -> &mut Computer {
+        self.cpu
+    }
+
+    // Handlers may only use their operands as declared.
+    fn check_operand(&self, idx: usize, kind: Operand) -> Result<(), ExecFault> {
+        match self.ext.operands().get(idx) {
+            None => Err(ExecFault::Ext(self.code(), "no such operand")),
+            Some(&k) if k != kind => Err(ExecFault::Ext(self.code(), "wrong operand kind")),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn check_io(&self) -> Result<(), ExecFault> {
+        if self.ext.does_io() { Ok(()) } else { Err(ExecFault::Ext(self.code(), "undeclared I/O")) }
+    }
+
+    // Division's failures: by zero, or MIN by -1.
+    fn arith(&self, val: Option<Word>, divisor: Word) -> Result<Word, ExecFault> {
+        val.ok_or_else(|| ExecFault::Ext(self.code(),
+                                         if divisor == 0 { "division by zero" } else { "overflow" }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::ExecError;
+    use crate::decode::{DecodeFault, Mode, OpcodeFault};
+    use crate::sched::TapeDev;
+
+    fn arith_isa() -> Arc<Isa> {
+        let mut isa = Isa::new();
+        isa.register(10, ExtInsn::div()).unwrap();
+        isa.register(11, ExtInsn::rem()).unwrap();
+        Arc::new(isa)
+    }
+
+    #[test]
+    fn registration() {
+        let mut isa = Isa::new();
+        assert_eq!(isa.register(1, ExtInsn::debug()), Err(IsaError::BuiltIn(1)));
+        assert_eq!(isa.register(99, ExtInsn::debug()), Err(IsaError::BuiltIn(99)));
+        assert_eq!(isa.register(100, ExtInsn::debug()), Err(IsaError::OutOfRange(100)));
+        assert_eq!(isa.register(-5, ExtInsn::debug()), Err(IsaError::OutOfRange(-5)));
+        assert_eq!(isa.register(42, ExtInsn::debug()), Ok(()));
+        assert_eq!(isa.register(42, ExtInsn::debug()), Err(IsaError::Taken(42)));
+        assert_eq!(isa.codes(), vec![42]);
+    }
+
+    #[test]
+    fn decode() {
+        let isa = arith_isa();
+        assert_eq!(Insn::decode_with(21010, Some(&isa)),
+                   Ok(Insn { opcode: Opcode::Ext { code: 10, len: 4 },
+                             modes: [Mode::Position, Mode::Immediate, Mode::Relative] }));
+        assert!(Insn::decode(10).is_err());
+        assert!(Insn::decode_with(12, Some(&isa)).is_err());
+        assert_eq!(Insn::decode_with(1001, Some(&isa)), Insn::decode(1001));
+    }
+
+    #[test]
+    fn divmod() {
+        // out(in() / 7); out(in() % 7)
+        let prog = vec![3,100,1010,100,7,101,4,101,3,100,1011,100,7,101,4,101,99];
+        let cpu = Computer::new(prog).with_isa(arith_isa());
+        let mut dev = TapeDev::new(&[-23, -23]);
+        cpu.clone().run(&mut dev).unwrap();
+        assert_eq!(dev.output, vec![-3, -2]);
+
+        let mut dev = TapeDev::new(&[5, 0]);
+        let mut cpu = Computer::new(vec![3,100,10,100,100,101,99]).with_isa(arith_isa());
+        cpu.run(&mut dev).unwrap();
+        assert_eq!(cpu.read(101), Ok(1));
+        let mut cpu = Computer::new(vec![3,100,1010,100,0,101,99]).with_isa(arith_isa());
+        assert_eq!(cpu.run(&mut dev),
+                   Err(ExecError { pc: 2, fault: ExecFault::Ext(10, "division by zero") }));
+        let mut cpu = Computer::new(vec![1010,5,-1,6,99,Word::MIN,0]).with_isa(arith_isa());
+        assert_eq!(cpu.run(&mut ()),
+                   Err(ExecError { pc: 0, fault: ExecFault::Ext(10, "overflow") }));
+        let mut cpu = Computer::new(vec![11010,1,1,0,99]).with_isa(arith_isa());
+        assert_eq!(cpu.run(&mut ()),
+                   Err(ExecError { pc: 0, fault: ExecFault::WriteImmediate }));
+    }
+
+    #[test]
+    fn without_isa() {
+        let mut cpu = Computer::new(vec![1010,1,1,0,99]);
+        assert_eq!(cpu.run(&mut ()),
+                   Err(ExecError { pc: 0, fault: ExecFault::Decode(
+                       DecodeFault::Opcode(OpcodeFault::Unknown(10))) }));
+    }
+
+    #[test]
+    fn trap() {
+        // Trap 0 reads twice and writes the sum; trap 1 jumps to the
+        // address in location 20; trap 2 halts.
+        let calls = Arc::new(Mutex::new(vec![]));
+        let log = calls.clone();
+        let mut isa = Isa::new();
+        isa.register(42, ExtInsn::trap(move |num, ctx| {
+            log.lock().unwrap().push((ctx.pc(), num));
+            match num {
+                0 => {
+                    let (x, y) = (ctx.input()?, ctx.input()?);
+                    ctx.output(x + y)
+                }
+                1 => {
+                    let target = ctx.computer().read(20)?;
+                    ctx.jump(target);
+                    Ok(())
+                }
+                2 => {
+                    ctx.halt();
+                    Ok(())
+                }
+                _ => Err(ExecFault::Ext(ctx.code(), "bad trap")),
+            }
+        }).with_io()).unwrap();
+        let isa = Arc::new(isa);
+
+        let mut prog = vec![142,0,142,1,104,0,142,2];
+        prog.resize(20, 0);
+        prog.push(6);
+        let mut cpu = Computer::new(prog).with_isa(isa.clone());
+        let mut dev = TapeDev::new(&[3, 4]);
+        cpu.run(&mut dev).unwrap();
+        assert_eq!(dev.output, vec![7]);
+        assert_eq!(*calls.lock().unwrap(), vec![(0, 0), (2, 1), (6, 2)]);
+
+        let mut cpu = Computer::new(vec![142,3,99]).with_isa(isa);
+        assert_eq!(cpu.run(&mut ()),
+                   Err(ExecError { pc: 0, fault: ExecFault::Ext(42, "bad trap") }));
+    }
+
+    #[test]
+    fn undeclared_io() {
+        let mut isa = Isa::new();
+        isa.register(42, ExtInsn::new("peek", &[], |ctx| ctx.input().map(drop))).unwrap();
+        let mut cpu = Computer::new(vec![42,99]).with_isa(Arc::new(isa));
+        assert_eq!(cpu.run(&mut TapeDev::new(&[1])),
+                   Err(ExecError { pc: 0, fault: ExecFault::Ext(42, "undeclared I/O") }));
+    }
+
+    #[test]
+    fn bad_operands() {
+        let mut isa = Isa::new();
+        let ops = [Operand::Read, Operand::Read, Operand::Write];
+        isa.register(40, ExtInsn::new("far", &ops, |ctx| ctx.read(3).map(drop))).unwrap();
+        isa.register(41, ExtInsn::new("farw", &ops, |ctx| ctx.write(3, 0))).unwrap();
+        isa.register(42, ExtInsn::new("wr", &ops, |ctx| ctx.write(0, 0))).unwrap();
+        isa.register(43, ExtInsn::new("rw", &ops, |ctx| ctx.read(2).map(drop))).unwrap();
+        let isa = Arc::new(isa);
+        for (code, fault) in [(40, "no such operand"), (41, "no such operand"),
+                              (42, "wrong operand kind"), (43, "wrong operand kind")] {
+            let mut cpu = Computer::new(vec![code,0,0,0,99]).with_isa(isa.clone());
+            assert_eq!(cpu.run(&mut ()),
+                       Err(ExecError { pc: 0, fault: ExecFault::Ext(code, fault) }));
+        }
+    }
+}
@@ -1,10 +1,26 @@
 use std::num::ParseIntError;
 use std::str::FromStr;
 
+pub mod asynch;
+pub mod callstack;
+pub mod cfg;
+pub mod chan;
 pub mod decode;
+pub mod decompile;
+pub mod disasm;
 pub mod exec;
+pub mod heatmap;
+pub mod isa;
+pub mod minimize;
+pub mod net;
+pub mod opt;
+pub mod pcap;
+#[cfg(test)]
+mod refmodel;
+pub mod sched;
 
-pub use exec::{Computer, Device, ExecError, IOError, Stepped};
+pub use chan::{ChannelDev, spawn_computer};
+pub use exec::{Computer, Device, ExecError, IOError, Stepped, TrapAction};
 
 pub type Word = i64;
 
@@ -69,6 +85,19 @@ mod test {
         assert_eq!(cpu.read(4).unwrap(), 99);
     }
 
+    #[test]
+    fn set_base_overflow() {
+        // arb MAX; arb 1 faults rather than wrapping or panicking, and
+        // leaves the base alone.
+        let mut cpu = Computer::new(vec![109,Word::MAX,109,1,99]);
+        assert_eq!(cpu.step(&mut ()), Ok(Stepped::Ok));
+        assert_eq!(cpu.step(&mut ()), Err(ExecError {
+            pc: 2,
+            fault: exec::ExecFault::Overflow(exec::ArithOp::Lea, Word::MAX, 1),
+        }));
+        assert_eq!((cpu.pc(), cpu.base()), (2, Word::MAX));
+    }
+
     fn day2_case(before: Vec<Word>, after: Vec<Word>) {
         let mut cpu = Computer::new(before);
         cpu.run(&mut ()).unwrap();
@@ -183,4 +212,87 @@ mod test {
         cpu.run(&mut dev).unwrap();
         dev.expect(vec![1125899906842624]);
     }
+
+    #[test]
+    fn trap_skip_negative() {
+        use exec::{ExecFault, MemFault, MemMode};
+        use std::sync::{Arc, Mutex};
+
+        // Reads from -1, then outputs 7.
+        let prog = vec![1,-1,0,9,104,7,99];
+        let mut cpu = Computer::new(prog.clone());
+        assert_eq!(cpu.run(&mut ()).unwrap_err().fault,
+                   ExecFault::Mem(MemFault { addr: -1, mode: MemMode::DRead }));
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let log = seen.clone();
+        let mut cpu = Computer::new(prog).with_trap(move |err, _cpu| {
+            log.lock().unwrap().push(err.clone());
+            TrapAction::Skip
+        });
+        let mut dev = TestDev::new(vec![]);
+        cpu.run(&mut dev).unwrap();
+        dev.expect(vec![7]);
+        assert_eq!(seen.lock().unwrap().len(), 1);
+        assert_eq!(seen.lock().unwrap()[0].pc, 0);
+    }
+
+    #[test]
+    fn trap_emulate() {
+        // Opcode 10 is "double [a]", emulated by the handler; anything
+        // else aborts.
+        let cpu = Computer::new(vec![10,7,1102,3,5,8,99,21,0]).with_trap(|err, cpu| {
+            let pc = err.pc;
+            if cpu.read(pc) != Ok(10) {
+                return TrapAction::Abort;
+            }
+            let addr = cpu.read(pc + 1).unwrap();
+            let val = cpu.read(addr).unwrap();
+            cpu.write(addr, val * 2).unwrap();
+            TrapAction::Jump(pc + 2)
+        });
+        let mut ok = cpu.clone();
+        ok.run(&mut ()).unwrap();
+        assert_eq!(ok.into_mem(), vec![10,7,1102,3,5,8,99,42,15]);
+
+        let mut bad = cpu.with_trap(|_, _| TrapAction::Abort);
+        bad.write(0, 12).unwrap();
+        assert_eq!(bad.run(&mut ()).unwrap_err().pc, 0);
+    }
+
+    #[test]
+    fn trap_retry() {
+        let mut cpu = Computer::new(vec![55,0,0,0,4,0,99]).with_trap(|err, cpu| {
+            cpu.write(err.pc, 1101).unwrap();
+            TrapAction::Retry
+        });
+        let mut dev = TestDev::new(vec![]);
+        cpu.run(&mut dev).unwrap();
+        dev.expect(vec![0]);
+    }
+
+    #[test]
+    fn trap_ignores_io() {
+        let mut cpu = Computer::new(vec![3,0,99]).with_trap(|_, _| TrapAction::Skip);
+        assert_eq!(cpu.run(&mut ()).unwrap_err().fault, exec::ExecFault::IO(IOError));
+    }
+
+    #[test]
+    fn registers() {
+        let mut cpu = Computer::with_state(4, 10, vec![204,1,99,0,109,-7,204,-1,99]);
+        assert_eq!((cpu.pc(), cpu.base(), cpu.mem_len()), (4, 10, 9));
+        let mut dev = TestDev::new(vec![]);
+        cpu.run(&mut dev).unwrap();
+        dev.expect(vec![99]);
+        assert_eq!((cpu.pc(), cpu.base()), (8, 3));
+
+        cpu.set_pc(0);
+        cpu.set_base(-1);
+        let mut dev = TestDev::new(vec![]);
+        cpu.run(&mut dev).unwrap();
+        dev.expect(vec![204]);
+        cpu.write(20, 5).unwrap();
+        assert_eq!(cpu.mem_len(), 21);
+        assert_eq!(&cpu.mem()[..4], &[204,1,99,0]);
+    }
 }
@@ -0,0 +1,196 @@
+use std::fmt::Write;
+
+use crate::{Computer, Word};
+use crate::exec::{ExecFault, Stepped};
+use crate::sched::TapeDev;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub mem: Vec<Word>,
+    pub input: Vec<Word>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ending {
+    Halted,
+    Fault(ExecFault),
+    TimedOut,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub output: Vec<Word>,
+    pub ending: Ending,
+}
+
+fn words(ws: &[Word]) -> String {
+    ws.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(",")
+}
+
+impl Case {
+    pub fn new(mem: Vec<Word>, input: Vec<Word>) -> Self {
+        Self { mem, input }
+    }
+
+    // Shrinking can easily turn a program into an infinite loop, so
+    // runs are cut off after `limit` steps.
+    pub fn run(&self, limit: usize) -> Outcome {
+        let mut cpu = Computer::new(self.mem.clone());
+        let mut dev = TapeDev::new(&self.input);
+        let mut ending = Ending::TimedOut;
+        for _ in 0..limit {
+            match cpu.step(&mut dev) {
+                Ok(Stepped::Ok) => (),
+                Ok(Stepped::Halted) => { ending = Ending::Halted; break }
+                Err(err) => { ending = Ending::Fault(err.fault); break }
+            }
+        }
+        Outcome { output: dev.output, ending }
+    }
+
+    // A test in the style of the ones in lib.rs, asserting `expected`;
+    // for a wrong-output bug that's the right answer, not what the
+    // Computer did.
+    pub fn to_test(&self, name: &str, expected: &Outcome) -> String {
+        let mut s = String::new();
+        writeln!(s, "    #[test]").unwrap();
+        writeln!(s, "    fn {}() {{", name).unwrap();
+        writeln!(s, "        let mut dev = TestDev::new(vec![{}]);", words(&self.input)).unwrap();
+        writeln!(s, "        let mut cpu = Computer::new(vec![{}]);", words(&self.mem)).unwrap();
+        match &expected.ending {
+            Ending::Halted => {
+                writeln!(s, "        cpu.run(&mut dev).unwrap();").unwrap();
+            }
+            Ending::Fault(fault) => {
+                writeln!(s, "        let err = cpu.run(&mut dev).unwrap_err();").unwrap();
+                writeln!(s, "        assert_eq!(format!(\"{{:?}}\", err.fault), {:?});",
+                         format!("{:?}", fault)).unwrap();
+            }
+            Ending::TimedOut => {
+                writeln!(s, "        // Doesn't halt.").unwrap();
+                writeln!(s, "        for _ in 0..1000 {{").unwrap();
+                writeln!(s, "            assert_eq!(cpu.step(&mut dev), Ok(Stepped::Ok));").unwrap();
+                writeln!(s, "        }}").unwrap();
+            }
+        }
+        writeln!(s, "        assert_eq!(dev.out_tape, vec![{}]);", words(&expected.output)).unwrap();
+        writeln!(s, "    }}").unwrap();
+        s
+    }
+}
+
+// Delta debugging: tries removing ever-smaller chunks, keeping any
+// removal that leaves the case interesting.  Never tries an empty list
+// unless `allow_empty`.
+fn ddmin<F>(mut items: Vec<Word>, allow_empty: bool, test: &mut F) -> Vec<Word>
+    where F: FnMut(&[Word]) -> bool
+{
+    if allow_empty && !items.is_empty() && test(&[]) {
+        return vec![];
+    }
+    let mut n = 2;
+    while items.len() >= 2 {
+        let chunk = items.len().div_ceil(n);
+        let found = (0..items.len()).step_by(chunk).find_map(|start| {
+            let end = (start + chunk).min(items.len());
+            let cand: Vec<_> = items[..start].iter().chain(&items[end..]).cloned().collect();
+            if test(&cand) { Some(cand) } else { None }
+        });
+        match found {
+            Some(cand) => {
+                items = cand;
+                n = (n - 1).max(2);
+            }
+            None if n >= items.len() => break,
+            None => n = (n * 2).min(items.len()),
+        }
+    }
+    items
+}
+
+// Moves each word toward zero, by bisection between the smallest value
+// known to work and the largest known not to.
+fn simplify<F>(items: &mut [Word], test: &mut F)
+    where F: FnMut(&[Word]) -> bool
+{
+    for i in 0..items.len() {
+        let (mut lo, mut hi) = (0, items[i]);
+        items[i] = 0;
+        if hi == 0 || test(items) {
+            continue;
+        }
+        while hi.abs_diff(lo) > 1 {
+            let mid = lo + (hi - lo) / 2;
+            items[i] = mid;
+            if test(items) { hi = mid } else { lo = mid }
+        }
+        items[i] = hi;
+    }
+}
+
+pub fn minimize<P>(case: &Case, mut interesting: P) -> Case
+    where P: FnMut(&Case) -> bool
+{
+    assert!(interesting(case), "the original case isn't interesting");
+    let mut cur = case.clone();
+    loop {
+        let before = cur.clone();
+        let input = cur.input.clone();
+        cur.mem = ddmin(cur.mem, false, &mut |mem| interesting(&Case::new(mem.to_vec(), input.clone())));
+        let mem = cur.mem.clone();
+        cur.input = ddmin(cur.input, true, &mut |input| interesting(&Case::new(mem.clone(), input.to_vec())));
+        let input = cur.input.clone();
+        simplify(&mut cur.mem, &mut |mem| interesting(&Case::new(mem.to_vec(), input.clone())));
+        let mem = cur.mem.clone();
+        simplify(&mut cur.input, &mut |input| interesting(&Case::new(mem.clone(), input.to_vec())));
+        if cur == before {
+            return cur;
+        }
+    }
+}
+
+// The usual predicate: the same fault, wherever it happens.
+pub fn same_fault(fault: ExecFault, limit: usize) -> impl FnMut(&Case) -> bool {
+    move |case| case.run(limit).ending == Ending::Fault(fault.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exec::{MemFault, MemMode};
+
+    #[test]
+    fn negative_read() {
+        let case = Case::new(vec![1101,0,0,20,1001,20,5,20,104,7,3,30,1,-1,0,0,99], vec![5, 6]);
+        let fault = ExecFault::Mem(MemFault { addr: -1, mode: MemMode::DRead });
+        assert_eq!(case.run(100).ending, Ending::Fault(fault.clone()));
+        let min = minimize(&case, same_fault(fault, 100));
+        assert_eq!(min, Case::new(vec![1,-1], vec![]));
+    }
+
+    #[test]
+    fn wrong_output() {
+        // Pretend that outputting anything over 100 is a bug.
+        let case = Case::new(vec![3,20,1002,20,3,20,4,20,104,1000,99], vec![40]);
+        let min = minimize(&case, |case| case.run(100).output.iter().any(|&w| w > 100));
+        assert_eq!(min, Case::new(vec![104,101], vec![]));
+    }
+
+    #[test]
+    fn format() {
+        let case = Case::new(vec![1,-1], vec![3]);
+        let out = case.run(10);
+        assert_eq!(case.to_test("min_fault", &out), "    #[test]
+    fn min_fault() {
+        let mut dev = TestDev::new(vec![3]);
+        let mut cpu = Computer::new(vec![1,-1]);
+        let err = cpu.run(&mut dev).unwrap_err();
+        assert_eq!(format!(\"{:?}\", err.fault), \"Mem(MemFault { addr: -1, mode: DRead })\");
+        assert_eq!(dev.out_tape, vec![]);
+    }
+");
+        let case = Case::new(vec![104,5,99], vec![]);
+        let out = case.run(10);
+        assert!(case.to_test("halts", &out).contains("cpu.run(&mut dev).unwrap();\n        assert_eq!(dev.out_tape, vec![5]);"));
+    }
+}
@@ -0,0 +1,627 @@
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use crate::{Computer, Device, ExecError, IOError, Word};
+use crate::pcap::{Record, SharedCapture};
+use crate::sched::{Event, Policy, Pollable, SchedError, Scheduler, Task, TaskState};
+
+pub type Addr = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub src: Addr,
+    pub dst: Addr,
+    pub data: Vec<Word>,
+}
+
+// Which nodes may send to which.  Anything sent to an address outside
+// the network (including off the end of a chain) goes to the outbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Topology {
+    Chain(usize),
+    Ring(usize),
+    Crossbar(usize),
+    Graph(usize, Vec<(Addr, Addr)>),
+}
+
+impl Topology {
+    pub fn size(&self) -> usize {
+        match *self {
+            Topology::Chain(n) | Topology::Ring(n) | Topology::Crossbar(n) | Topology::Graph(n, _) => n
+        }
+    }
+
+    pub fn linked(&self, src: Addr, dst: Addr) -> bool {
+        let n = self.size();
+        if src >= n || dst >= n {
+            return src < n;
+        }
+        match self {
+            Topology::Chain(_) => dst == src + 1,
+            Topology::Ring(_) => dst == (src + 1) % n,
+            Topology::Crossbar(_) => true,
+            Topology::Graph(_, edges) => edges.contains(&(src, dst)),
+        }
+    }
+
+    pub fn successors(&self, src: Addr) -> Vec<Addr> {
+        (0..self.size()).filter(|&dst| self.linked(src, dst)).collect()
+    }
+}
+
+// How a node's output words are cut into packets, and what it reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    // Each word output is a packet to every successor (or the outbox,
+    // if there are none); input blocks until a word arrives.
+    Stream,
+    // Output is a destination address followed by `len` words, and
+    // input is the payload words in order.  If `idle` is set, reading
+    // from an empty queue returns it instead of blocking.
+    Addressed { len: usize, idle: Option<Word> },
+}
+
+impl Framing {
+    fn frame_len(self) -> usize {
+        match self {
+            Framing::Stream => 1,
+            Framing::Addressed { len, .. } => len + 1,
+        }
+    }
+
+    fn idle(self) -> Option<Word> {
+        match self {
+            Framing::Stream => None,
+            Framing::Addressed { idle, .. } => idle,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetError {
+    Exec(Addr, ExecError),
+    NoRoute { src: Addr, dst: Word },
+    Starved(Addr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Busy,
+    // Every node is halted, or blocked or idle with nothing queued.
+    Idle,
+    Halted,
+}
+
+fn capture(cap: &Option<SharedCapture>, tick: u64, pkt: &Packet) {
+    if let Some(cap) = cap {
+        let rec = Record { tick, src: pkt.src as Word, dst: pkt.dst as Word, data: pkt.data.clone() };
+        cap.lock().unwrap().record(&rec);
+    }
+}
+
+fn route(topo: &Topology, framing: Framing, src: Addr, mut frame: Vec<Word>)
+         -> Result<Vec<Packet>, NetError>
+{
+    match framing {
+        Framing::Stream => {
+            let dsts = topo.successors(src);
+            if dsts.is_empty() {
+                return Ok(vec![Packet { src, dst: topo.size(), data: frame }]);
+            }
+            Ok(dsts.into_iter().map(|dst| Packet { src, dst, data: frame.clone() }).collect())
+        }
+        Framing::Addressed { .. } => {
+            let data = frame.split_off(1);
+            let wdst = frame[0];
+            if wdst < 0 || !topo.linked(src, wdst as Addr) {
+                return Err(NetError::NoRoute { src, dst: wdst });
+            }
+            Ok(vec![Packet { src, dst: wdst as Addr, data }])
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Port {
+    framing: Framing,
+    queue: VecDeque<Word>,
+    frame: Vec<Word>,
+    sent: Vec<Vec<Word>>,
+    waiting: bool,
+}
+
+impl Port {
+    fn new(framing: Framing) -> Self {
+        Self {
+            framing,
+            queue: VecDeque::new(),
+            frame: vec![],
+            sent: vec![],
+            waiting: false,
+        }
+    }
+}
+
+impl Device for Port {
+    fn input(&mut self) -> Result<Word, IOError> {
+        if let Some(w) = self.queue.pop_front() {
+            self.waiting = false;
+            return Ok(w);
+        }
+        self.waiting = true;
+        self.framing.idle().ok_or(IOError)
+    }
+
+    fn output(&mut self, val: Word) -> Result<(), IOError> {
+        self.frame.push(val);
+        if self.frame.len() == self.framing.frame_len() {
+            self.sent.push(mem::take(&mut self.frame));
+        }
+        Ok(())
+    }
+}
+
+impl Pollable for Port {
+    fn can_input(&self) -> bool {
+        !self.queue.is_empty() || self.framing.idle().is_some()
+    }
+}
+
+impl From<SchedError> for NetError {
+    fn from(inner: SchedError) -> Self {
+        match inner {
+            SchedError::Exec(addr, err) => NetError::Exec(addr, err),
+            SchedError::Starved(addr) => NetError::Starved(addr),
+            SchedError::Deadlock(_) => unreachable!("networks go idle instead"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Network {
+    topo: Topology,
+    framing: Framing,
+    sched: Scheduler<Port>,
+    outbox: VecDeque<Packet>,
+    capture: Option<SharedCapture>,
+    ticks: u64,
+}
+
+impl Network {
+    pub fn new(cpu: &Computer, topo: Topology, framing: Framing) -> Self {
+        let cpus = (0..topo.size()).map(|_| cpu.clone()).collect();
+        Self::from_computers(cpus, topo, framing)
+    }
+
+    pub fn from_computers(cpus: Vec<Computer>, topo: Topology, framing: Framing) -> Self {
+        assert_eq!(cpus.len(), topo.size());
+        let mut sched = Scheduler::new();
+        for cpu in cpus {
+            sched.spawn(cpu, Port::new(framing));
+        }
+        Self { topo, framing, sched, outbox: VecDeque::new(), capture: None, ticks: 0 }
+    }
+
+    pub fn with_starve_limit(self, slices: usize) -> Self {
+        Self { sched: self.sched.with_starve_limit(slices), ..self }
+    }
+
+    // Every packet routed from now on is also recorded here, with the
+    // number of slices run so far as its tick.
+    pub fn capture_to(&mut self, cap: SharedCapture) {
+        self.capture = Some(cap);
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn size(&self) -> usize {
+        self.sched.len()
+    }
+
+    pub fn topology(&self) -> &Topology {
+        &self.topo
+    }
+
+    pub fn computer(&self, addr: Addr) -> &Computer {
+        &self.sched.task(addr).cpu
+    }
+
+    pub fn into_computers(mut self) -> Vec<Computer> {
+        self.sched.drain().into_iter().map(|task| task.cpu).collect()
+    }
+
+    pub fn queue(&self, addr: Addr) -> &VecDeque<Word> {
+        &self.sched.task(addr).dev.queue
+    }
+
+    pub fn is_halted(&self, addr: Addr) -> bool {
+        self.sched.task(addr).state == TaskState::Halted
+    }
+
+    pub fn is_waiting(&self, addr: Addr) -> bool {
+        let task = self.sched.task(addr);
+        match task.state {
+            // Nothing queued to a halted node will ever be read.
+            TaskState::Halted => true,
+            TaskState::Blocked => task.dev.queue.is_empty(),
+            TaskState::Ready => task.dev.waiting && task.dev.queue.is_empty(),
+        }
+    }
+
+    // Words sent in from outside, with no framing applied.
+    pub fn inject(&mut self, addr: Addr, words: &[Word]) {
+        let port = &mut self.sched.task_mut(addr).dev;
+        port.queue.extend(words);
+        port.waiting = false;
+    }
+
+    // Packets that left the network.
+    pub fn recv(&mut self) -> Option<Packet> {
+        self.outbox.pop_front()
+    }
+
+    pub fn status(&self) -> Status {
+        if (0..self.size()).all(|addr| self.is_halted(addr)) {
+            Status::Halted
+        } else if (0..self.size()).all(|addr| self.is_waiting(addr)) {
+            Status::Idle
+        } else {
+            Status::Busy
+        }
+    }
+
+    fn deliver(&mut self, pkt: Packet) {
+        if pkt.dst < self.size() {
+            self.inject(pkt.dst, &pkt.data);
+        } else {
+            self.outbox.push_back(pkt);
+        }
+    }
+
+    // Runs one slice of whichever node the policy picks, then sends
+    // whatever it output.
+    pub fn slice(&mut self, policy: &mut dyn Policy) -> Result<Status, NetError> {
+        if let Event::Ran(addr, _) = self.sched.slice(policy)? {
+            self.ticks += 1;
+            for frame in mem::take(&mut self.sched.task_mut(addr).dev.sent) {
+                for pkt in route(&self.topo, self.framing, addr, frame)? {
+                    capture(&self.capture, self.ticks, &pkt);
+                    self.deliver(pkt);
+                }
+            }
+        }
+        Ok(self.status())
+    }
+
+    // Runs until every node is idle (or blocked) or halted.
+    pub fn run(&mut self, policy: &mut dyn Policy) -> Result<Status, NetError> {
+        loop {
+            match self.slice(policy)? {
+                Status::Busy => (),
+                status => return Ok(status),
+            }
+        }
+    }
+
+    // Runs every node on its own thread until the network goes idle or
+    // halts.  Nodes that were blocked are stopped at their next input,
+    // so the network can be resumed afterwards.  The whole run counts
+    // as one tick.
+    pub fn run_threaded(&mut self) -> Result<Status, NetError> {
+        let tasks = self.sched.drain();
+        let fabric = Fabric {
+            queues: tasks.iter().map(|task| task.dev.queue.clone()).collect(),
+            waiting: tasks.iter().map(|task| task.dev.waiting).collect(),
+            halted: tasks.iter().map(|task| task.state == TaskState::Halted).collect(),
+            outbox: mem::take(&mut self.outbox),
+            error: None,
+            stop: false,
+        };
+        let shared = Arc::new((Mutex::new(fabric), Condvar::new()));
+        let mut joins = vec![];
+        for (addr, task) in tasks.into_iter().enumerate() {
+            let mut port = ThreadPort {
+                addr,
+                topo: self.topo.clone(),
+                framing: self.framing,
+                frame: task.dev.frame,
+                shared: shared.clone(),
+                capture: self.capture.clone(),
+                tick: self.ticks + 1,
+            };
+            let mut cpu = task.cpu;
+            let halted = task.state == TaskState::Halted;
+            joins.push(thread::spawn(move || {
+                if !halted {
+                    let result = cpu.run(&mut port);
+                    port.finish(result);
+                }
+                (cpu, port.frame)
+            }));
+        }
+
+        let (lock, cond) = &*shared;
+        let mut fab = lock.lock().unwrap();
+        let status = loop {
+            if fab.error.is_some() {
+                break Status::Busy;
+            }
+            if fab.halted.iter().all(|&h| h) {
+                break Status::Halted;
+            }
+            if fab.is_idle() {
+                break Status::Idle;
+            }
+            fab = cond.wait(fab).unwrap();
+        };
+        fab.stop = true;
+        cond.notify_all();
+        drop(fab);
+
+        let results: Vec<_> = joins.into_iter()
+            .map(|join| join.join().expect("network node panicked"))
+            .collect();
+        let mut fab = lock.lock().unwrap();
+        for (addr, (cpu, frame)) in results.into_iter().enumerate() {
+            let mut dev = Port::new(self.framing);
+            dev.frame = frame;
+            dev.queue = mem::take(&mut fab.queues[addr]);
+            dev.waiting = fab.waiting[addr];
+            let state = if fab.halted[addr] { TaskState::Halted } else { TaskState::Ready };
+            self.sched.push(Task { cpu, dev, state });
+        }
+        self.outbox = mem::take(&mut fab.outbox);
+        self.ticks += 1;
+        match fab.error.take() {
+            Some(err) => Err(err),
+            None => Ok(status),
+        }
+    }
+}
+
+struct Fabric {
+    queues: Vec<VecDeque<Word>>,
+    waiting: Vec<bool>,
+    halted: Vec<bool>,
+    outbox: VecDeque<Packet>,
+    error: Option<NetError>,
+    stop: bool,
+}
+
+impl Fabric {
+    fn is_idle(&self) -> bool {
+        (0..self.queues.len()).all(|a| self.halted[a] || self.waiting[a] && self.queues[a].is_empty())
+    }
+
+    fn fail(&mut self, err: NetError) {
+        if self.error.is_none() {
+            self.error = Some(err);
+        }
+        self.stop = true;
+    }
+}
+
+type Shared = Arc<(Mutex<Fabric>, Condvar)>;
+
+struct ThreadPort {
+    addr: Addr,
+    topo: Topology,
+    framing: Framing,
+    frame: Vec<Word>,
+    shared: Shared,
+    capture: Option<SharedCapture>,
+    tick: u64,
+}
+
+impl ThreadPort {
+    fn finish(&mut self, result: Result<(), ExecError>) {
+        let (lock, cond) = &*self.shared;
+        let mut fab = lock.lock().unwrap();
+        match result {
+            Ok(()) => fab.halted[self.addr] = true,
+            // Faults after the stop are just the stop itself (or a
+            // consequence of some other node's failure).
+            Err(err) => if !fab.stop {
+                fab.fail(NetError::Exec(self.addr, err));
+            }
+        }
+        cond.notify_all();
+    }
+}
+
+impl Device for ThreadPort {
+    fn input(&mut self) -> Result<Word, IOError> {
+        let (lock, cond) = &*self.shared;
+        let mut fab = lock.lock().unwrap();
+        loop {
+            if fab.stop {
+                return Err(IOError);
+            }
+            if let Some(w) = fab.queues[self.addr].pop_front() {
+                fab.waiting[self.addr] = false;
+                return Ok(w);
+            }
+            fab.waiting[self.addr] = true;
+            cond.notify_all();
+            if let Some(w) = self.framing.idle() {
+                return Ok(w);
+            }
+            fab = cond.wait(fab).unwrap();
+        }
+    }
+
+    fn output(&mut self, val: Word) -> Result<(), IOError> {
+        self.frame.push(val);
+        if self.frame.len() < self.framing.frame_len() {
+            return Ok(());
+        }
+        let frame = mem::take(&mut self.frame);
+        let (lock, cond) = &*self.shared;
+        let mut fab = lock.lock().unwrap();
+        let result = route(&self.topo, self.framing, self.addr, frame);
+        cond.notify_all();
+        match result {
+            Ok(pkts) => {
+                for pkt in pkts {
+                    capture(&self.capture, self.tick, &pkt);
+                    if pkt.dst < fab.queues.len() {
+                        fab.queues[pkt.dst].extend(&pkt.data);
+                        fab.waiting[pkt.dst] = false;
+                    } else {
+                        fab.outbox.push_back(pkt);
+                    }
+                }
+                Ok(())
+            }
+            Err(err) => {
+                fab.fail(err);
+                Err(IOError)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sched::RoundRobin;
+
+    fn doubler() -> Computer {
+        // loop { out(in() * 2) }
+        Computer::new(vec![3,11,1002,11,2,11,4,11,1105,1,0])
+    }
+
+    fn forwarder() -> Computer {
+        // loop { out(in()); out(in()); out(in()) }
+        Computer::new(vec![3,100,4,100,3,100,4,100,3,100,4,100,1105,1,0])
+    }
+
+    fn poller() -> Computer {
+        // loop { x = in(); if x != -1 { y = in(); out(y); out(x + 1); out(y + 1) } }
+        Computer::new(vec![3,100,1008,100,-1,102,1005,102,0,3,101,4,101,
+                           1001,100,1,100,4,100,1001,101,1,101,4,101,1105,1,0])
+    }
+
+    #[test]
+    fn chain_rr() {
+        let mut net = Network::new(&doubler(), Topology::Chain(3), Framing::Stream);
+        net.inject(0, &[1, 2, 3]);
+        assert_eq!(net.run(&mut RoundRobin::new(1)), Ok(Status::Idle));
+        let outs: Vec<_> = (0..3).map(|_| net.recv().unwrap()).collect();
+        assert_eq!(outs.iter().map(|p| p.data[0]).collect::<Vec<_>>(), vec![8, 16, 24]);
+        assert!(outs.iter().all(|p| p.src == 2 && p.dst == 3));
+        assert_eq!(net.recv(), None);
+        net.inject(0, &[4]);
+        assert_eq!(net.run(&mut RoundRobin::new(100)), Ok(Status::Idle));
+        assert_eq!(net.recv().unwrap().data, vec![32]);
+    }
+
+    #[test]
+    fn chain_threaded() {
+        let mut net = Network::new(&doubler(), Topology::Chain(4), Framing::Stream);
+        net.inject(0, &[1, 2, 3]);
+        assert_eq!(net.run_threaded(), Ok(Status::Idle));
+        let outs: Vec<_> = (0..3).map(|_| net.recv().unwrap().data[0]).collect();
+        assert_eq!(outs, vec![16, 32, 48]);
+        // And it can be resumed, either way.
+        net.inject(0, &[5]);
+        assert_eq!(net.run(&mut RoundRobin::new(1)), Ok(Status::Idle));
+        assert_eq!(net.recv().unwrap().data, vec![80]);
+        net.inject(0, &[6]);
+        assert_eq!(net.run_threaded(), Ok(Status::Idle));
+        assert_eq!(net.recv().unwrap().data, vec![96]);
+    }
+
+    #[test]
+    fn ring_halts() {
+        // in(); loop 3 times { out(in() + 1) }; halt
+        let cpu = Computer::new(vec![3,100,3,100,1001,100,1,100,4,100,
+                                     1001,101,1,101,1008,101,3,102,1006,102,2,99]);
+        let mut net = Network::new(&cpu, Topology::Ring(2), Framing::Stream);
+        net.inject(0, &[-1, 0]);
+        net.inject(1, &[-1]);
+        assert_eq!(net.run(&mut RoundRobin::new(1)), Ok(Status::Halted));
+        assert_eq!(net.queue(0).iter().cloned().collect::<Vec<_>>(), vec![6]);
+    }
+
+    #[test]
+    fn send_to_halted() {
+        // if in() == 0 { halt }; loop { out(in()) }
+        let cpu = Computer::new(vec![3,100,1005,100,6,99,3,100,4,100,1105,1,6]);
+        let mut net = Network::new(&cpu, Topology::Chain(2), Framing::Stream);
+        let mut tnet = net.clone();
+        net.inject(1, &[0]);
+        net.inject(0, &[1]);
+        assert_eq!(net.run(&mut RoundRobin::new(1)), Ok(Status::Idle));
+        assert!(net.is_halted(1));
+        net.inject(0, &[7]);
+        assert_eq!(net.run(&mut RoundRobin::new(1)), Ok(Status::Idle));
+        assert_eq!(net.queue(1).iter().cloned().collect::<Vec<_>>(), vec![7]);
+        assert!(net.is_waiting(1));
+
+        tnet.inject(1, &[0]);
+        tnet.inject(0, &[1, 7]);
+        assert_eq!(tnet.run_threaded(), Ok(Status::Idle));
+        assert_eq!(tnet.queue(1).iter().cloned().collect::<Vec<_>>(), vec![7]);
+    }
+
+    #[test]
+    fn crossbar_addressed() {
+        let framing = Framing::Addressed { len: 2, idle: Some(-1) };
+        let mut net = Network::new(&poller(), Topology::Crossbar(3), framing);
+        let mut tnet = net.clone();
+        net.inject(0, &[10, 1]);
+        assert_eq!(net.run(&mut RoundRobin::new(1)), Ok(Status::Idle));
+        // 0 -> 1 (11, 2) -> 2 (12, 3) -> 3 = outside
+        assert_eq!(net.recv(), Some(Packet { src: 2, dst: 3, data: vec![13, 4] }));
+        assert_eq!(net.recv(), None);
+
+        tnet.inject(0, &[10, 1]);
+        assert_eq!(tnet.run_threaded(), Ok(Status::Idle));
+        assert_eq!(tnet.recv(), Some(Packet { src: 2, dst: 3, data: vec![13, 4] }));
+        assert_eq!(tnet.recv(), None);
+    }
+
+    #[test]
+    fn captures() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut net = Network::new(&doubler(), Topology::Chain(2), Framing::Stream);
+        net.capture_to(log.clone());
+        net.inject(0, &[1, 2]);
+        assert_eq!(net.run(&mut RoundRobin::new(100)), Ok(Status::Idle));
+        net.inject(0, &[3]);
+        assert_eq!(net.run_threaded(), Ok(Status::Idle));
+        let recs: Vec<_> = log.lock().unwrap().iter()
+            .map(|rec: &Record| (rec.src, rec.dst, rec.data[0]))
+            .collect();
+        assert_eq!(recs, vec![(0, 1, 2), (0, 1, 4), (1, 2, 4), (1, 2, 8), (0, 1, 6), (1, 2, 12)]);
+        assert_eq!(log.lock().unwrap().last().unwrap().tick, net.ticks());
+    }
+
+    #[test]
+    fn graph_no_route() {
+        let topo = Topology::Graph(2, vec![(0, 1)]);
+        let framing = Framing::Addressed { len: 2, idle: None };
+        let mut net = Network::new(&forwarder(), topo.clone(), framing);
+        net.inject(1, &[0, 1, 2]);
+        assert_eq!(net.run(&mut RoundRobin::new(1)), Err(NetError::NoRoute { src: 1, dst: 0 }));
+        let mut net = Network::new(&forwarder(), topo, framing);
+        net.inject(1, &[0, 1, 2]);
+        assert_eq!(net.run_threaded(), Err(NetError::NoRoute { src: 1, dst: 0 }));
+    }
+
+    #[test]
+    fn threaded_fault() {
+        let bad = Computer::new(vec![3,100,4,-1]);
+        let mut net = Network::new(&bad, Topology::Chain(2), Framing::Stream);
+        net.inject(0, &[1]);
+        match net.run_threaded() {
+            Err(NetError::Exec(0, err)) => assert_eq!(err.pc, 2),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::BTreeSet;
+
+use crate::Word;
+use crate::cfg::{Cfg, CfgError, Exit, encode};
+use crate::decode::{Insn, Mode, Opcode};
+use crate::minimize::{Case, Outcome};
+
+// Addresses in Intcode can't be told apart from other numbers, so
+// nothing is moved: dead code is zeroed out instead, and the image only
+// gets shorter when that leaves zeros at the end (memory past the end
+// reads as zero anyway).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub folded: usize,
+    pub threaded: usize,
+    pub removed: usize,
+}
+
+fn word(mem: &[Word], addr: Word) -> Word {
+    mem.get(addr as usize).copied().unwrap_or(0)
+}
+
+// Instructions that are also data can't be rewritten.
+fn volatile(cfg: &Cfg, addr: Word, len: Word) -> bool {
+    (addr..addr + len).any(|a| cfg.written.contains(&a) || cfg.read.contains(&a))
+}
+
+// `add a, b -> c` or `mul a, b -> c` on immediates becomes `add a*b, 0 -> c`.
+fn fold(mem: &mut [Word], addr: Word, insn: &Insn) -> bool {
+    if insn.modes[..2] != [Mode::Immediate; 2] || insn.modes[2] == Mode::Immediate {
+        return false;
+    }
+    let (x, y) = (word(mem, addr + 1), word(mem, addr + 2));
+    let val = match insn.opcode {
+        Opcode::Add if y != 0 => x.checked_add(y),
+        Opcode::Mul => x.checked_mul(y),
+        _ => None,
+    };
+    match val {
+        Some(val) => {
+            let modes = [Mode::Immediate, Mode::Immediate, insn.modes[2]];
+            mem[addr as usize] = encode(1, modes);
+            mem[addr as usize + 1] = val;
+            mem[addr as usize + 2] = 0;
+            true
+        }
+        None => false,
+    }
+}
+
+// Where a jump to `target` really ends up, skipping over blocks that
+// are nothing but an unconditional jump.
+fn final_target(cfg: &Cfg, mut target: Word) -> Word {
+    let mut seen = BTreeSet::new();
+    while seen.insert(target) {
+        match cfg.block(target) {
+            Some(b) if b.insns.len() == 1 && !volatile(cfg, b.start, b.end - b.start) => match b.exit {
+                Exit::Jump(next) => target = next,
+                _ => break,
+            },
+            _ => break,
+        }
+    }
+    target
+}
+
+fn thread(cfg: &Cfg, mem: &mut [Word], addr: Word, insn: &Insn, exit: Exit) -> bool {
+    let before = mem[addr as usize..addr as usize + 3].to_vec();
+    let a = addr as usize;
+    match exit {
+        Exit::Fall(_) => {
+            // Never taken, so the target is irrelevant.
+            mem[a] = encode(5, [Mode::Immediate, Mode::Immediate, Mode::Position]);
+            mem[a + 1] = 0;
+            mem[a + 2] = 0;
+        }
+        Exit::Jump(target) => {
+            mem[a] = encode(5, [Mode::Immediate, Mode::Immediate, Mode::Position]);
+            mem[a + 1] = 1;
+            mem[a + 2] = final_target(cfg, target);
+        }
+        Exit::Indirect { fall: None } => {
+            mem[a] = encode(5, [Mode::Immediate, insn.modes[1], Mode::Position]);
+            mem[a + 1] = 1;
+        }
+        Exit::Branch { taken, .. } => mem[a + 2] = final_target(cfg, taken),
+        _ => (),
+    }
+    mem[a..a + 3] != before[..]
+}
+
+// Maximal runs of words that aren't part of any reachable instruction.
+fn dead_runs(cfg: &Cfg, len: Word) -> Vec<(Word, Word)> {
+    let mut runs = vec![];
+    let mut addr = 0;
+    while addr < len {
+        if cfg.covers(addr) {
+            addr += 1;
+            continue;
+        }
+        let start = addr;
+        while addr < len && !cfg.covers(addr) {
+            addr += 1;
+        }
+        runs.push((start, addr));
+    }
+    runs
+}
+
+// Zeroes runs of dead words that nothing outside them refers to; a run
+// that might be data is likely to have its address somewhere.
+fn sweep(cfg: &Cfg, mem: &mut [Word]) -> usize {
+    let mut removed = 0;
+    for (start, end) in dead_runs(cfg, mem.len() as Word) {
+        let referenced = mem.iter().enumerate()
+            .filter(|&(i, _)| (i as Word) < start || i as Word >= end)
+            .any(|(_, &w)| start <= w && w < end);
+        if !referenced {
+            for w in &mut mem[start as usize..end as usize] {
+                if *w != 0 {
+                    *w = 0;
+                    removed += 1;
+                }
+            }
+        }
+    }
+    removed
+}
+
+pub fn optimize(orig: &[Word]) -> Result<(Vec<Word>, Report), CfgError> {
+    let mut mem = orig.to_vec();
+    let mut report = Report::default();
+    let cfg = Cfg::build(&mem)?;
+    // Room for an instruction that hangs off the end; it's trimmed later.
+    mem.resize(orig.len() + 3, 0);
+    for block in cfg.blocks.values() {
+        for &addr in &block.insns {
+            let insn = match Insn::decode(word(&mem, addr)) {
+                Ok(insn) => insn,
+                Err(_) => continue,
+            };
+            if volatile(&cfg, addr, insn.opcode.len()) {
+                continue;
+            }
+            match insn.opcode {
+                Opcode::Add | Opcode::Mul if fold(&mut mem, addr, &insn) =>
+                    report.folded += 1,
+                Opcode::Jnz | Opcode::Jz if thread(&cfg, &mut mem, addr, &insn, block.exit) =>
+                    report.threaded += 1,
+                _ => (),
+            }
+        }
+    }
+    // Zeroing some code can make more unreferenced.
+    loop {
+        let removed = sweep(&Cfg::build(&mem)?, &mut mem);
+        if removed == 0 {
+            break;
+        }
+        report.removed += removed;
+    }
+    let len = mem.iter().rposition(|&w| w != 0).map_or(1, |i| i + 1);
+    mem.truncate(len);
+    Ok((mem, report))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub input: Vec<Word>,
+    pub before: Outcome,
+    pub after: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invalid {
+    // Checking against no inputs at all would prove nothing.
+    NoTapes,
+    Mismatch(Box<Mismatch>),
+}
+
+// The analysis rests on assumptions about how programs use memory, so
+// an optimized image should be checked against the original on some
+// representative inputs.
+pub fn validate(orig: &[Word], opt: &[Word], tapes: &[Vec<Word>], limit: usize) -> Result<(), Invalid> {
+    if tapes.is_empty() {
+        return Err(Invalid::NoTapes);
+    }
+    for input in tapes {
+        let before = Case::new(orig.to_vec(), input.clone()).run(limit);
+        let after = Case::new(opt.to_vec(), input.clone()).run(limit);
+        if before != after {
+            return Err(Invalid::Mismatch(Box::new(Mismatch { input: input.clone(), before, after })));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check(orig: &[Word], tapes: &[Vec<Word>]) -> (Vec<Word>, Report) {
+        let (opt, report) = optimize(orig).unwrap();
+        validate(orig, &opt, tapes, 10000).unwrap();
+        (opt, report)
+    }
+
+    #[test]
+    fn folding() {
+        let (opt, report) = check(&[1102,6,7,13,4,13,1101,1,-1,14,4,14,99], &[vec![]]);
+        assert_eq!(opt, vec![1101,42,0,13,4,13,1101,0,0,14,4,14,99]);
+        assert_eq!(report, Report { folded: 2, threaded: 0, removed: 0 });
+        // Overflow is left to fault at run time.
+        let (opt, _) = check(&[1102,Word::MAX,2,5,99], &[vec![]]);
+        assert_eq!(opt, vec![1102,Word::MAX,2,5,99]);
+    }
+
+    #[test]
+    fn threading() {
+        let (opt, report) = check(&[1105,1,6,104,1,99,1105,1,9,104,2,99], &[vec![]]);
+        assert_eq!(opt, vec![1105,1,9,0,0,0,0,0,0,104,2,99]);
+        assert_eq!(report, Report { folded: 0, threaded: 1, removed: 6 });
+
+        let (opt, report) = check(&[1106,1,6,104,3,99,104,4,99], &[vec![]]);
+        assert_eq!(opt, vec![1105,0,0,104,3,99]);
+        assert_eq!(report, Report { folded: 0, threaded: 1, removed: 3 });
+    }
+
+    #[test]
+    fn referenced_data() {
+        let orig = [4,7,99,104,5,99,0,42];
+        assert_eq!(check(&orig, &[vec![]]).0, orig.to_vec());
+        // Running off the end, which is zeros and doesn't decode.
+        assert_eq!(check(&[21101,2,3], &[vec![]]).0, vec![21101,5]);
+    }
+
+    #[test]
+    fn code_as_data() {
+        // Outputs the target of the jump at 5, so that can't be skipped.
+        let orig = [4,7,1105,1,5,1105,1,9,0,99];
+        assert_eq!(check(&orig, &[vec![]]), (orig.to_vec(), Report::default()));
+        let (opt, report) = check(&[4,8,1105,1,5,1105,1,9,0,99], &[vec![]]);
+        assert_eq!(opt, vec![4,8,1105,1,9,1105,1,9,0,99]);
+        assert_eq!(report, Report { folded: 0, threaded: 1, removed: 0 });
+    }
+
+    #[test]
+    fn day5() {
+        let orig = [3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+                    1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+                    999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99];
+        let (opt, report) = check(&orig, &[vec![7], vec![8], vec![9]]);
+        assert_eq!(report, Report { folded: 1, threaded: 1, removed: 1 });
+        assert_eq!(&opt[16..19], &[1105,1,36]);
+        assert_eq!(opt[45], 0);
+        assert_eq!(&opt[36..40], &[1101,1001,0,20]);
+    }
+
+    #[test]
+    fn mismatch() {
+        let err = match validate(&[3,0,4,0,99], &[3,0,104,0,99], &[vec![1], vec![2]], 100) {
+            Err(Invalid::Mismatch(err)) => err,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(err.input, vec![1]);
+        assert_eq!((err.before.output, err.after.output), (vec![1], vec![0]));
+        assert_eq!(optimize(&[1,9,10,3,2,3,11,0,99,30,40,50]).unwrap_err(), CfgError::SelfModifying(0));
+    }
+
+    #[test]
+    fn needs_tapes() {
+        // The relative read of the 42 is invisible to the analysis, so
+        // only running the program shows that optimizing broke it.
+        let orig = [109,5,109,5,204,0,99,0,0,0,42];
+        let (opt, _) = optimize(&orig).unwrap();
+        assert_eq!(validate(&orig, &opt, &[], 100), Err(Invalid::NoTapes));
+        assert!(matches!(validate(&orig, &opt, &[vec![]], 100), Err(Invalid::Mismatch(_))));
+    }
+}
@@ -0,0 +1,198 @@
+use std::io::{self, Read, Write};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+
+use crate::Word;
+
+// Capture files are a header (magic and version), then records of a
+// little-endian u64 tick, i64 source and destination, u32 payload
+// length, and that many i64 payload words.
+const MAGIC: &[u8; 4] = b"ICAP";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub tick: u64,
+    pub src: Word,
+    pub dst: Word,
+    pub data: Vec<Word>,
+}
+
+pub trait Capture: Send {
+    fn record(&mut self, rec: &Record);
+}
+
+pub type SharedCapture = Arc<Mutex<dyn Capture>>;
+
+impl Capture for Vec<Record> {
+    fn record(&mut self, rec: &Record) {
+        self.push(rec.clone())
+    }
+}
+
+// Write errors are held until `finish`, so that capturing can't
+// interfere with whatever is being captured.
+pub struct PcapWriter<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> PcapWriter<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        Ok(Self { out, error: None })
+    }
+
+    fn write_record(&mut self, rec: &Record) -> io::Result<()> {
+        self.out.write_all(&rec.tick.to_le_bytes())?;
+        self.out.write_all(&rec.src.to_le_bytes())?;
+        self.out.write_all(&rec.dst.to_le_bytes())?;
+        self.out.write_all(&(rec.data.len() as u32).to_le_bytes())?;
+        for w in &rec.data {
+            self.out.write_all(&w.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.out.flush()
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl<W: Write + Send> Capture for PcapWriter<W> {
+    fn record(&mut self, rec: &Record) {
+        if self.error.is_none() {
+            self.error = self.write_record(rec).err();
+        }
+    }
+}
+
+pub struct PcapReader<R: Read> {
+    inp: R,
+}
+
+fn bad_data(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+impl<R: Read> PcapReader<R> {
+    pub fn new(mut inp: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        inp.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(bad_data("not a capture file"));
+        }
+        let mut version = [0; 4];
+        inp.read_exact(&mut version)?;
+        if u32::from_le_bytes(version) != VERSION {
+            return Err(bad_data("unknown capture version"));
+        }
+        Ok(Self { inp })
+    }
+
+    fn read_u64(&mut self) -> io::Result<u64> {
+        let mut buf = [0; 8];
+        self.inp.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<Record>> {
+        let mut buf = [0; 8];
+        // A clean end of file can only come between records.
+        let mut got = 0;
+        while got < buf.len() {
+            match self.inp.read(&mut buf[got..])? {
+                0 if got == 0 => return Ok(None),
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => got += n,
+            }
+        }
+        let tick = u64::from_le_bytes(buf);
+        let src = self.read_u64()? as Word;
+        let dst = self.read_u64()? as Word;
+        let mut len = [0; 4];
+        self.inp.read_exact(&mut len)?;
+        let data = (0..u32::from_le_bytes(len))
+            .map(|_| self.read_u64().map(|w| w as Word))
+            .collect::<io::Result<_>>()?;
+        Ok(Some(Record { tick, src, dst, data }))
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<R> {
+    type Item = io::Result<Record>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub src: Option<Word>,
+    pub dst: Option<Word>,
+    pub ticks: Option<RangeInclusive<u64>>,
+}
+
+impl Filter {
+    pub fn matches(&self, rec: &Record) -> bool {
+        self.src.is_none_or(|src| src == rec.src)
+            && self.dst.is_none_or(|dst| dst == rec.dst)
+            && self.ticks.as_ref().is_none_or(|ticks| ticks.contains(&rec.tick))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> Vec<Record> {
+        vec![Record { tick: 0, src: 3, dst: 255, data: vec![17, -4] },
+             Record { tick: 9, src: 255, dst: 0, data: vec![17, -4] },
+             Record { tick: 9, src: 1, dst: 2, data: vec![] },
+             Record { tick: 1 << 40, src: 0, dst: 1, data: vec![Word::MIN, Word::MAX] }]
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut w = PcapWriter::new(vec![]).unwrap();
+        for rec in sample() {
+            w.record(&rec);
+        }
+        let bytes = w.finish().unwrap();
+        let recs: Vec<_> = PcapReader::new(&bytes[..]).unwrap().collect::<io::Result<_>>().unwrap();
+        assert_eq!(recs, sample());
+    }
+
+    #[test]
+    fn truncated() {
+        let mut w = PcapWriter::new(vec![]).unwrap();
+        w.record(&sample()[0]);
+        let bytes = w.finish().unwrap();
+        for cut in 9..bytes.len() {
+            let mut r = PcapReader::new(&bytes[..cut]).unwrap();
+            assert!(r.next().unwrap().is_err(), "cut at {}", cut);
+        }
+        assert!(PcapReader::new(&b"ICAP\x02\0\0\0"[..]).is_err());
+        assert!(PcapReader::new(&b"PCAP\x01\0\0\0"[..]).is_err());
+    }
+
+    #[test]
+    fn filters() {
+        let pick = |filter: Filter| -> Vec<u64> {
+            sample().iter().filter(|rec| filter.matches(rec)).map(|rec| rec.tick).collect()
+        };
+        assert_eq!(pick(Filter::default()).len(), 4);
+        assert_eq!(pick(Filter { dst: Some(255), ..Filter::default() }), vec![0]);
+        assert_eq!(pick(Filter { src: Some(255), ..Filter::default() }), vec![9]);
+        assert_eq!(pick(Filter { ticks: Some(1..=99), ..Filter::default() }), vec![9, 9]);
+    }
+}
@@ -0,0 +1,274 @@
+// A deliberately naive Intcode interpreter, written straight from the
+// puzzle text with no sharing of code with `exec`, and a differential
+// test that runs random programs on it and on Computer in lockstep.
+
+use quickcheck::{Arbitrary, Gen};
+use quickcheck_macros::quickcheck;
+
+use crate::{Computer, Word};
+use crate::decode::{DecodeFault, ModeFault, OpcodeFault};
+use crate::exec::{ArithOp, ExecError, ExecFault, MemFault, MemMode, Stepped};
+use crate::sched::TapeDev;
+
+// Writes beyond this aren't compared, so that random programs can't
+// make either side allocate absurd amounts of memory.
+const MEM_LIMIT: Word = 1 << 16;
+
+const STEP_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Running,
+    Halted,
+    Fault(ExecFault),
+    TooBig,
+}
+
+struct Model {
+    pc: Word,
+    base: Word,
+    mem: Vec<Word>,
+    input: Vec<Word>,
+    output: Vec<Word>,
+}
+
+fn mem_fault(addr: Word, mode: MemMode) -> ExecFault {
+    ExecFault::Mem(MemFault { addr, mode })
+}
+
+impl Model {
+    fn new(mem: &[Word], input: &[Word]) -> Self {
+        Self { pc: 0, base: 0, mem: mem.to_vec(), input: input.to_vec(), output: vec![] }
+    }
+
+    fn peek(&self, addr: Word) -> Word {
+        if (addr as usize) < self.mem.len() { self.mem[addr as usize] } else { 0 }
+    }
+
+    fn fetch(&self, addr: Word) -> Result<Word, ExecFault> {
+        if addr < 0 { Err(mem_fault(addr, MemMode::IRead)) } else { Ok(self.peek(addr)) }
+    }
+
+    fn load(&self, addr: Word) -> Result<Word, ExecFault> {
+        if addr < 0 { Err(mem_fault(addr, MemMode::DRead)) } else { Ok(self.peek(addr)) }
+    }
+
+    fn mode(insn: Word, i: Word) -> Word {
+        insn / [100, 1000, 10000][i as usize] % 10
+    }
+
+    fn relative(&self, field: Word) -> Result<Word, ExecFault> {
+        match self.base.checked_add(field) {
+            Some(addr) => Ok(addr),
+            None => Err(ExecFault::Overflow(ArithOp::Lea, self.base, field)),
+        }
+    }
+
+    fn arg(&self, insn: Word, i: Word) -> Result<Word, ExecFault> {
+        let field = self.fetch(self.pc + 1 + i)?;
+        match Self::mode(insn, i) {
+            0 => self.load(field),
+            1 => Ok(field),
+            _ => self.load(self.relative(field)?),
+        }
+    }
+
+    fn dest(&self, insn: Word, i: Word) -> Result<Word, ExecFault> {
+        let field = self.fetch(self.pc + 1 + i)?;
+        let addr = match Self::mode(insn, i) {
+            0 => field,
+            1 => return Err(ExecFault::WriteImmediate),
+            _ => self.relative(field)?,
+        };
+        if addr < 0 {
+            return Err(mem_fault(addr, MemMode::DWrite));
+        }
+        Ok(addr)
+    }
+
+    // Returns false if the write is too big to bother with.
+    fn store(&mut self, addr: Word, val: Word) -> bool {
+        if addr > MEM_LIMIT {
+            return false;
+        }
+        while self.mem.len() <= addr as usize {
+            self.mem.push(0);
+        }
+        self.mem[addr as usize] = val;
+        true
+    }
+
+    fn decode(insn: Word) -> Result<Word, ExecFault> {
+        if insn < 0 {
+            return Err(ExecFault::Decode(DecodeFault::Negative(insn)));
+        }
+        let op = insn % 100;
+        if !(1..=9).contains(&op) && op != 99 {
+            return Err(ExecFault::Decode(DecodeFault::Opcode(OpcodeFault::Unknown(op))));
+        }
+        for i in 0..3 {
+            let m = Self::mode(insn, i);
+            if m > 2 {
+                let fault = ModeFault::Unknown(m);
+                return Err(ExecFault::Decode(DecodeFault::Mode { param: i as usize, fault }));
+            }
+        }
+        if insn / 100000 != 0 {
+            return Err(ExecFault::Decode(DecodeFault::ReservedNonZero(insn / 100000)));
+        }
+        Ok(op)
+    }
+
+    fn step(&mut self) -> Outcome {
+        match self.try_step() {
+            Ok(outcome) => outcome,
+            Err(fault) => Outcome::Fault(fault),
+        }
+    }
+
+    fn try_step(&mut self) -> Result<Outcome, ExecFault> {
+        let insn = self.fetch(self.pc)?;
+        let op = Self::decode(insn)?;
+        let (val, dest, next) = match op {
+            1 | 2 | 7 | 8 => {
+                let x = self.arg(insn, 0)?;
+                let y = self.arg(insn, 1)?;
+                let val = match op {
+                    1 => x.checked_add(y).ok_or(ExecFault::Overflow(ArithOp::Add, x, y))?,
+                    2 => x.checked_mul(y).ok_or(ExecFault::Overflow(ArithOp::Mul, x, y))?,
+                    7 => (x < y) as Word,
+                    _ => (x == y) as Word,
+                };
+                (val, self.dest(insn, 2)?, self.pc + 4)
+            }
+            3 => {
+                let val = match self.input.pop() {
+                    Some(val) => val,
+                    None => return Err(ExecFault::IO(crate::IOError)),
+                };
+                (val, self.dest(insn, 0)?, self.pc + 2)
+            }
+            4 => {
+                let val = self.arg(insn, 0)?;
+                self.output.push(val);
+                self.pc += 2;
+                return Ok(Outcome::Running);
+            }
+            5 | 6 => {
+                let x = self.arg(insn, 0)?;
+                self.pc = if (x != 0) == (op == 5) { self.arg(insn, 1)? } else { self.pc + 3 };
+                return Ok(Outcome::Running);
+            }
+            9 => {
+                let x = self.arg(insn, 0)?;
+                self.base = self.relative(x)?;
+                self.pc += 2;
+                return Ok(Outcome::Running);
+            }
+            _ => return Ok(Outcome::Halted),
+        };
+        if !self.store(dest, val) {
+            return Ok(Outcome::TooBig);
+        }
+        self.pc = next;
+        Ok(Outcome::Running)
+    }
+}
+
+fn trim(mem: &[Word]) -> &[Word] {
+    let len = mem.iter().rposition(|&w| w != 0).map_or(0, |i| i + 1);
+    &mem[..len]
+}
+
+// Panics with the first difference.
+fn compare(mem: &[Word], input: &[Word]) {
+    let mut model = Model::new(mem, input);
+    model.input.reverse();
+    let mut cpu = Computer::new(mem.to_vec());
+    let mut dev = TapeDev::new(input);
+    for step in 0..STEP_LIMIT {
+        let pc = model.pc;
+        let expected = model.step();
+        let got = match expected {
+            Outcome::TooBig => return,
+            _ => cpu.step(&mut dev),
+        };
+        let got = match got {
+            Ok(Stepped::Ok) => Outcome::Running,
+            Ok(Stepped::Halted) => Outcome::Halted,
+            Err(ExecError { pc: epc, fault }) => {
+                assert_eq!(epc, pc, "fault pc at step {}", step);
+                Outcome::Fault(fault)
+            }
+        };
+        assert_eq!(got, expected, "outcome at step {}", step);
+        assert_eq!((cpu.pc(), cpu.base()), (model.pc, model.base), "registers at step {}", step);
+        assert_eq!(trim(cpu.mem()), trim(&model.mem), "memory at step {}", step);
+        assert_eq!(dev.output, model.output, "output at step {}", step);
+        assert_eq!(dev.input, model.input, "input at step {}", step);
+        if got != Outcome::Running {
+            return;
+        }
+    }
+}
+
+fn pick<G: Gen>(g: &mut G, n: usize) -> usize {
+    usize::arbitrary(g) % n
+}
+
+// Mostly well-formed instructions with small operands, so that they
+// refer to each other, with the occasional bad encoding or huge value.
+#[derive(Debug, Clone)]
+struct Prog {
+    mem: Vec<Word>,
+    input: Vec<Word>,
+}
+
+impl Arbitrary for Prog {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        const OPS: [Word; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+        const EDGES: [Word; 6] = [Word::MIN, Word::MIN + 1, -1, Word::MAX - 1, Word::MAX, MEM_LIMIT];
+        let mut mem = vec![];
+        for _ in 0..1 + pick(g, 24) {
+            let mut insn = if pick(g, 40) == 0 { pick(g, 100) as Word } else { OPS[pick(g, OPS.len())] };
+            for scale in &[100, 1000, 10000] {
+                let mode = if pick(g, 60) == 0 { 3 + pick(g, 7) } else { pick(g, 3) };
+                insn += scale * mode as Word;
+            }
+            if pick(g, 60) == 0 {
+                insn += 100000;
+            }
+            if pick(g, 60) == 0 {
+                insn = -insn;
+            }
+            mem.push(insn);
+            for _ in 0..3 {
+                mem.push(if pick(g, 30) == 0 { EDGES[pick(g, EDGES.len())] } else { Word::arbitrary(g) % 64 });
+            }
+        }
+        let input = (0..pick(g, 8)).map(|_| Word::arbitrary(g)).collect();
+        Prog { mem, input }
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new((self.mem.clone(), self.input.clone()).shrink()
+                 .filter(|(mem, _)| !mem.is_empty())
+                 .map(|(mem, input)| Prog { mem, input }))
+    }
+}
+
+#[quickcheck]
+fn qc_matches_reference(prog: Prog) {
+    compare(&prog.mem, &prog.input);
+}
+
+#[test]
+fn known_programs() {
+    compare(&[109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99], &[]);
+    compare(&[3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+              1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+              999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99], &[9]);
+    compare(&[1102,34915192,34915192,7,4,7,99,0], &[]);
+    compare(&[109,Word::MAX,109,1,99], &[]);
+    compare(&[11101,1,1,0,3,-1], &[]);
+}
@@ -0,0 +1,412 @@
+use crate::{Computer, Device, ExecError, IOError, Word};
+use crate::exec::Stepped;
+
+// A Device that can say ahead of time whether input would succeed, so
+// that a Computer waiting for input can be set aside instead of failing.
+pub trait Pollable: Device {
+    fn can_input(&self) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    Ready,
+    Blocked,
+    Halted,
+}
+
+// How a slice ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ran {
+    Preempted,
+    Blocked,
+    Halted,
+}
+
+// Runs up to `quantum` instructions, stopping before any input that
+// would fail.  Returns how the slice ended and how many instructions
+// were executed.
+pub fn run_slice<D>(cpu: &mut Computer, dev: &mut D, quantum: usize) -> Result<(Ran, usize), ExecError>
+    where D: Pollable
+{
+    for steps in 0..quantum {
+        if cpu.input_insn().is_some() && !dev.can_input() {
+            return Ok((Ran::Blocked, steps));
+        }
+        if cpu.step(dev)? == Stepped::Halted {
+            return Ok((Ran::Halted, steps));
+        }
+    }
+    Ok((Ran::Preempted, quantum))
+}
+
+pub trait Policy {
+    // Chooses one of the ready tasks (never empty, in increasing order)
+    // to run next, and the most instructions it may run.
+    fn pick(&mut self, ready: &[usize]) -> (usize, usize);
+}
+
+// Each ready task in turn.
+#[derive(Debug, Clone)]
+pub struct RoundRobin {
+    quantum: usize,
+    next: usize,
+}
+
+impl RoundRobin {
+    pub fn new(quantum: usize) -> Self {
+        assert!(quantum > 0);
+        Self { quantum, next: 0 }
+    }
+}
+
+impl Policy for RoundRobin {
+    fn pick(&mut self, ready: &[usize]) -> (usize, usize) {
+        let id = ready.iter().cloned().find(|&id| id >= self.next).unwrap_or(ready[0]);
+        self.next = id + 1;
+        (id, self.quantum)
+    }
+}
+
+// Keeps running the same task until it blocks or halts; `chunk` only
+// bounds how long it runs between checks for starvation.
+#[derive(Debug, Clone)]
+pub struct RunUntilBlock {
+    chunk: usize,
+    current: Option<usize>,
+}
+
+impl RunUntilBlock {
+    pub fn new(chunk: usize) -> Self {
+        assert!(chunk > 0);
+        Self { chunk, current: None }
+    }
+}
+
+impl Policy for RunUntilBlock {
+    fn pick(&mut self, ready: &[usize]) -> (usize, usize) {
+        let id = self.current.filter(|id| ready.contains(id)).unwrap_or(ready[0]);
+        self.current = Some(id);
+        (id, self.chunk)
+    }
+}
+
+// Uniformly random among the ready tasks, from a fixed seed so runs
+// can be reproduced.
+#[derive(Debug, Clone)]
+pub struct Random {
+    quantum: usize,
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64, quantum: usize) -> Self {
+        assert!(quantum > 0);
+        // Xorshift gets stuck at zero.
+        Self { quantum, state: seed | 1 }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+impl Policy for Random {
+    fn pick(&mut self, ready: &[usize]) -> (usize, usize) {
+        let i = (self.next_u64() % ready.len() as u64) as usize;
+        (ready[i], self.quantum)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchedError {
+    Exec(usize, ExecError),
+    // Every task that hasn't halted is blocked on input.
+    Deadlock(Vec<usize>),
+    // A ready task went too many slices without being run.
+    Starved(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Ran(usize, Ran),
+    Idle,
+    Halted,
+}
+
+#[derive(Clone)]
+pub struct Task<D> {
+    pub cpu: Computer,
+    pub dev: D,
+    pub state: TaskState,
+}
+
+#[derive(Clone)]
+pub struct Scheduler<D> {
+    tasks: Vec<Task<D>>,
+    waited: Vec<usize>,
+    starve_limit: Option<usize>,
+}
+
+impl<D: Pollable> Scheduler<D> {
+    pub fn new() -> Self {
+        Self { tasks: vec![], waited: vec![], starve_limit: None }
+    }
+
+    pub fn with_starve_limit(self, slices: usize) -> Self {
+        Self { starve_limit: Some(slices), ..self }
+    }
+
+    pub fn push(&mut self, task: Task<D>) -> usize {
+        self.tasks.push(task);
+        self.waited.push(0);
+        self.tasks.len() - 1
+    }
+
+    pub fn spawn(&mut self, cpu: Computer, dev: D) -> usize {
+        self.push(Task { cpu, dev, state: TaskState::Ready })
+    }
+
+    pub fn drain(&mut self) -> Vec<Task<D>> {
+        self.waited.clear();
+        self.tasks.drain(..).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn task(&self, id: usize) -> &Task<D> {
+        &self.tasks[id]
+    }
+
+    pub fn task_mut(&mut self, id: usize) -> &mut Task<D> {
+        &mut self.tasks[id]
+    }
+
+    pub fn tasks(&self) -> impl Iterator<Item = &Task<D>> {
+        self.tasks.iter()
+    }
+
+    // Blocked tasks whose input has since arrived become ready again.
+    fn wake(&mut self) {
+        for task in &mut self.tasks {
+            if task.state == TaskState::Blocked && task.dev.can_input() {
+                task.state = TaskState::Ready;
+            }
+        }
+    }
+
+    fn ids_in(&self, state: TaskState) -> Vec<usize> {
+        (0..self.tasks.len()).filter(|&id| self.tasks[id].state == state).collect()
+    }
+
+    // Runs a given task for one slice, regardless of policy.
+    pub fn run_task(&mut self, id: usize, quantum: usize) -> Result<Ran, SchedError> {
+        let task = &mut self.tasks[id];
+        let ran = match task.state {
+            TaskState::Halted => Ran::Halted,
+            _ => run_slice(&mut task.cpu, &mut task.dev, quantum)
+                .map_err(|err| SchedError::Exec(id, err))?.0,
+        };
+        task.state = match ran {
+            Ran::Preempted => TaskState::Ready,
+            Ran::Blocked => TaskState::Blocked,
+            Ran::Halted => TaskState::Halted,
+        };
+        Ok(ran)
+    }
+
+    pub fn slice(&mut self, policy: &mut dyn Policy) -> Result<Event, SchedError> {
+        self.wake();
+        let ready = self.ids_in(TaskState::Ready);
+        if ready.is_empty() {
+            return Ok(if self.tasks.iter().all(|task| task.state == TaskState::Halted) {
+                Event::Halted
+            } else {
+                Event::Idle
+            });
+        }
+        let (id, quantum) = policy.pick(&ready);
+        debug_assert!(ready.contains(&id));
+        for &other in &ready {
+            self.waited[other] += 1;
+        }
+        self.waited[id] = 0;
+        if let Some(limit) = self.starve_limit {
+            if let Some(&starved) = ready.iter().find(|&&other| self.waited[other] > limit) {
+                return Err(SchedError::Starved(starved));
+            }
+        }
+        Ok(Event::Ran(id, self.run_task(id, quantum)?))
+    }
+
+    // Runs until every task is blocked or halted.
+    pub fn run_until_idle(&mut self, policy: &mut dyn Policy) -> Result<Event, SchedError> {
+        loop {
+            match self.slice(policy)? {
+                Event::Ran(..) => (),
+                event => return Ok(event),
+            }
+        }
+    }
+
+    // Runs until every task halts.
+    pub fn run(&mut self, policy: &mut dyn Policy) -> Result<(), SchedError> {
+        match self.run_until_idle(policy)? {
+            Event::Halted => Ok(()),
+            _ => Err(SchedError::Deadlock(self.ids_in(TaskState::Blocked))),
+        }
+    }
+}
+
+impl<D: Pollable> Default for Scheduler<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Input from a tape, which can run out; mostly for testing.
+#[derive(Debug, Clone, Default)]
+pub struct TapeDev {
+    pub input: Vec<Word>,
+    pub output: Vec<Word>,
+}
+
+impl TapeDev {
+    pub fn new(input: &[Word]) -> Self {
+        Self { input: input.iter().rev().cloned().collect(), output: vec![] }
+    }
+}
+
+impl Device for TapeDev {
+    fn input(&mut self) -> Result<Word, IOError> {
+        self.input.pop().ok_or(IOError)
+    }
+    fn output(&mut self, val: Word) -> Result<(), IOError> {
+        self.output.push(val);
+        Ok(())
+    }
+}
+
+impl Pollable for TapeDev {
+    fn can_input(&self) -> bool {
+        !self.input.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use crate::isa::{ExtInsn, Isa, Operand};
+
+    fn counter(n: Word) -> Computer {
+        // for i in 0..n { out(i) }
+        Computer::new(vec![4,100,1001,100,1,100,1007,100,n,101,1005,101,0,99])
+    }
+
+    fn doubler() -> Computer {
+        // loop { out(in() * 2) }
+        Computer::new(vec![3,11,1002,11,2,11,4,11,1105,1,0])
+    }
+
+    fn outputs(sched: &Scheduler<TapeDev>) -> Vec<Vec<Word>> {
+        sched.tasks().map(|task| task.dev.output.clone()).collect()
+    }
+
+    #[test]
+    fn round_robin_interleaves() {
+        let mut sched = Scheduler::new();
+        sched.spawn(counter(3), TapeDev::new(&[]));
+        sched.spawn(counter(5), TapeDev::new(&[]));
+        let mut order = vec![];
+        let mut policy = RoundRobin::new(4);
+        loop {
+            match sched.slice(&mut policy) {
+                Ok(Event::Ran(id, _)) => order.push(id),
+                Ok(Event::Halted) => break,
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert_eq!(order, vec![0, 1, 0, 1, 0, 1, 0, 1, 1, 1]);
+        assert_eq!(outputs(&sched), vec![vec![0, 1, 2], vec![0, 1, 2, 3, 4]]);
+    }
+
+    #[test]
+    fn run_until_block() {
+        let mut sched = Scheduler::new();
+        sched.spawn(doubler(), TapeDev::new(&[1, 2]));
+        sched.spawn(counter(2), TapeDev::new(&[]));
+        let mut policy = RunUntilBlock::new(1000);
+        assert_eq!(sched.slice(&mut policy), Ok(Event::Ran(0, Ran::Blocked)));
+        assert_eq!(sched.slice(&mut policy), Ok(Event::Ran(1, Ran::Halted)));
+        assert_eq!(sched.slice(&mut policy), Ok(Event::Idle));
+        assert_eq!(outputs(&sched), vec![vec![2, 4], vec![0, 1]]);
+        assert_eq!(sched.run(&mut policy), Err(SchedError::Deadlock(vec![0])));
+
+        sched.task_mut(0).dev.input.push(5);
+        assert_eq!(sched.run_until_idle(&mut policy), Ok(Event::Idle));
+        assert_eq!(sched.task(0).dev.output, vec![2, 4, 10]);
+    }
+
+    #[test]
+    fn random_is_reproducible() {
+        let trace = |seed| {
+            let mut sched = Scheduler::new();
+            for n in 1..5 {
+                sched.spawn(counter(n), TapeDev::new(&[]));
+            }
+            let mut policy = Random::new(seed, 2);
+            let mut order = vec![];
+            while let Ok(Event::Ran(id, _)) = sched.slice(&mut policy) {
+                order.push(id);
+            }
+            assert!(sched.tasks().all(|task| task.state == TaskState::Halted));
+            order
+        };
+        assert_eq!(trace(17), trace(17));
+        assert_ne!(trace(17), trace(18));
+    }
+
+    #[test]
+    fn starvation() {
+        let spin = Computer::new(vec![1105,1,0]);
+        let mut sched = Scheduler::new().with_starve_limit(10);
+        sched.spawn(spin.clone(), TapeDev::new(&[]));
+        sched.spawn(spin, TapeDev::new(&[]));
+        assert_eq!(sched.run(&mut RunUntilBlock::new(100)), Err(SchedError::Starved(1)));
+    }
+
+    #[test]
+    fn exec_fault() {
+        let mut sched = Scheduler::new();
+        sched.spawn(counter(2), TapeDev::new(&[]));
+        sched.spawn(Computer::new(vec![4,-1]), TapeDev::new(&[]));
+        match sched.run(&mut RoundRobin::new(1)) {
+            Err(SchedError::Exec(1, err)) => assert_eq!(err.pc, 0),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn extension_input() {
+        let mut isa = Isa::new();
+        isa.register(42, ExtInsn::new("get", &[Operand::Write], |ctx| {
+            let val = ctx.input()?;
+            ctx.write(0, val)
+        }).with_io()).unwrap();
+        let mut cpu = Computer::new(vec![42,5,4,5,99,0]).with_isa(Arc::new(isa));
+        let mut dev = TapeDev::new(&[]);
+        assert_eq!(run_slice(&mut cpu, &mut dev, 10), Ok((Ran::Blocked, 0)));
+        let mut dev = TapeDev::new(&[7]);
+        assert_eq!(run_slice(&mut cpu, &mut dev, 10), Ok((Ran::Halted, 2)));
+        assert_eq!(dev.output, vec![7]);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeSet;
 use std::ops::{Add, AddAssign, Sub, Mul};
 
 pub type Len = u32;
@@ -19,6 +20,15 @@ impl Point {
         Walker::new(self, dir, len)
     }
 
+    pub fn neighbors4(self) -> impl Iterator<Item = Point> {
+        Dir::ALL.iter().map(move |d| self + d.to_move())
+    }
+
+    // King moves: the orthogonal and diagonal neighbors.
+    pub fn neighbors8(self) -> impl Iterator<Item = Point> {
+        Dir8::ALL.iter().map(move |d| self + d.to_move())
+    }
+
     pub fn walk_many<Path>(self, path: Path) -> impl Iterator<Item = Point>
         where Path: IntoIterator<Item = (Dir, Len)>
     {
@@ -90,6 +100,30 @@ impl Move {
     pub fn turn_right(self) -> Move {
         Move { dx: self.dy, dy: -self.dx }
     }
+
+    // Counterclockwise quarter turns; negative is clockwise.
+    pub fn rotate(self, quarters: i32) -> Move {
+        match quarters.rem_euclid(4) {
+            0 => self,
+            1 => self.turn_left(),
+            2 => Move { dx: -self.dx, dy: -self.dy },
+            _ => self.turn_right(),
+        }
+    }
+
+    // Mirror images across the y and x axes respectively.
+    pub fn flip_x(self) -> Move {
+        Move { dx: -self.dx, dy: self.dy }
+    }
+
+    pub fn flip_y(self) -> Move {
+        Move { dx: self.dx, dy: -self.dy }
+    }
+
+    // The number of king moves it takes.
+    pub fn chebyshev_len(self) -> Len {
+        self.dx.unsigned_abs().max(self.dy.unsigned_abs())
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -101,6 +135,8 @@ pub enum Dir {
 }
 
 impl Dir {
+    pub const ALL: [Dir; 4] = [Dir::Rt, Dir::Up, Dir::Lf, Dir::Dn];
+
     pub fn to_move(self) -> Move {
         match self {
             Dir::Rt => Move { dx: 1, dy: 0 },
@@ -110,6 +146,10 @@ impl Dir {
         }
     }
 
+    pub fn from_move(m: Move) -> Option<Dir> {
+        Dir::ALL.iter().cloned().find(|d| d.to_move() == m)
+    }
+
     pub fn from_char(ch: char) -> Option<Dir> {
         match ch {
             'R' => Some(Dir::Rt),
@@ -120,6 +160,14 @@ impl Dir {
         }
     }
 
+    pub fn turn_left(self) -> Dir {
+        Dir::ALL[(self as usize + 1) % 4]
+    }
+
+    pub fn turn_right(self) -> Dir {
+        Dir::ALL[(self as usize + 3) % 4]
+    }
+
     pub fn rev(self) -> Dir {
         match self {
             Dir::Rt => Dir::Lf,
@@ -130,6 +178,242 @@ impl Dir {
     }
 }
 
+// The points Point::walk would visit (so not including the start), and
+// how far along the whole path the start is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Segment {
+    pub start: Point,
+    pub dir: Dir,
+    pub len: Len,
+    pub steps: Len,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Crossing {
+    pub at: Point,
+    // How far along each path the crossing is.
+    pub steps: (Len, Len),
+}
+
+impl Segment {
+    pub fn new(start: Point, dir: Dir, len: Len) -> Self {
+        Self { start, dir, len, steps: 0 }
+    }
+
+    pub fn path<Path>(start: Point, path: Path) -> Vec<Segment>
+        where Path: IntoIterator<Item = (Dir, Len)>
+    {
+        let mut here = Self::new(start, Dir::Rt, 0);
+        path.into_iter().map(|(dir, len)| {
+            here = Segment { start: here.end(), dir, len, steps: here.steps + here.len };
+            here
+        }).collect()
+    }
+
+    pub fn end(self) -> Point {
+        self.start + self.dir.to_move() * self.len
+    }
+
+    pub fn is_horizontal(self) -> bool {
+        self.dir == Dir::Rt || self.dir == Dir::Lf
+    }
+
+    // The low and high corners of the points covered; None if empty.
+    pub fn span(self) -> Option<(Point, Point)> {
+        if self.len == 0 {
+            return None;
+        }
+        let (a, b) = (self.start + self.dir.to_move(), self.end());
+        Some((Point { x: a.x.min(b.x), y: a.y.min(b.y) }, Point { x: a.x.max(b.x), y: a.y.max(b.y) }))
+    }
+
+    pub fn contains(self, p: Point) -> bool {
+        self.span().is_some_and(|(lo, hi)| lo.x <= p.x && p.x <= hi.x && lo.y <= p.y && p.y <= hi.y)
+    }
+
+    pub fn steps_to(self, p: Point) -> Option<Len> {
+        if self.contains(p) {
+            Some(self.steps + (p - self.start).len())
+        } else {
+            None
+        }
+    }
+
+    // The points in common, as low and high corners: a single point if
+    // the segments cross, or a run if they overlap end to end.
+    pub fn intersection(self, other: Segment) -> Option<(Point, Point)> {
+        let (alo, ahi) = self.span()?;
+        let (blo, bhi) = other.span()?;
+        let lo = Point { x: alo.x.max(blo.x), y: alo.y.max(blo.y) };
+        let hi = Point { x: ahi.x.min(bhi.x), y: ahi.y.min(bhi.y) };
+        if lo.x <= hi.x && lo.y <= hi.y {
+            Some((lo, hi))
+        } else {
+            None
+        }
+    }
+}
+
+// Every point where path a meets path b, once for each pair of
+// segments through it, in no particular order.  Perpendicular crossings
+// are found with a sweep line in O((n + k) log n) time; overlapping
+// collinear segments are assumed to be rare.
+pub fn crossings(a: &[Segment], b: &[Segment]) -> Vec<Crossing> {
+    let mut out = vec![];
+    sweep(a, b, &mut |i, j, at| out.push(Crossing { at, steps: (a[i].steps_to(at).unwrap(),
+                                                                 b[j].steps_to(at).unwrap()) }));
+    sweep(b, a, &mut |j, i, at| out.push(Crossing { at, steps: (a[i].steps_to(at).unwrap(),
+                                                                 b[j].steps_to(at).unwrap()) }));
+    for &horizontal in &[true, false] {
+        collinear(a, b, horizontal, &mut out);
+    }
+    out
+}
+
+// Horizontal segments of hs against vertical segments of vs, sweeping
+// from left to right: a horizontal segment is active between its ends,
+// and each vertical one looks up the active ones in its y range.
+fn sweep<F>(hs: &[Segment], vs: &[Segment], emit: &mut F)
+    where F: FnMut(usize, usize, Point)
+{
+    const ADD: u8 = 0;
+    const QUERY: u8 = 1;
+    const DROP: u8 = 2;
+    let mut events = vec![];
+    for (i, h) in hs.iter().enumerate().filter(|(_, h)| h.is_horizontal()) {
+        if let Some((lo, hi)) = h.span() {
+            events.push((lo.x, ADD, i));
+            events.push((hi.x, DROP, i));
+        }
+    }
+    for (j, v) in vs.iter().enumerate().filter(|(_, v)| !v.is_horizontal()) {
+        if v.len > 0 {
+            events.push((v.start.x, QUERY, j));
+        }
+    }
+    events.sort();
+    let mut active = BTreeSet::new();
+    for (x, kind, k) in events {
+        match kind {
+            ADD => { active.insert((hs[k].start.y, k)); }
+            DROP => { active.remove(&(hs[k].start.y, k)); }
+            _ => {
+                let (lo, hi) = vs[k].span().unwrap();
+                for &(y, i) in active.range((lo.y, 0)..=(hi.y, usize::MAX)) {
+                    emit(i, k, Point { x, y });
+                }
+            }
+        }
+    }
+}
+
+fn collinear(a: &[Segment], b: &[Segment], horizontal: bool, out: &mut Vec<Crossing>) {
+    // (line, low, high) in coordinates across and along the line.
+    let along = |p: Point| if horizontal { (p.y, p.x) } else { (p.x, p.y) };
+    let at = |line, t| if horizontal { Point { x: t, y: line } } else { Point { x: line, y: t } };
+    let mut spans = vec![];
+    for (w, segs) in [a, b].iter().enumerate() {
+        for (i, s) in segs.iter().enumerate().filter(|(_, s)| s.is_horizontal() == horizontal) {
+            if let Some((lo, hi)) = s.span() {
+                let (line, lo) = along(lo);
+                spans.push((line, lo, along(hi).1, w, i));
+            }
+        }
+    }
+    spans.sort();
+    let mut active: [Vec<(Coord, usize)>; 2] = [vec![], vec![]];
+    let mut cur_line = None;
+    for (line, lo, hi, w, i) in spans {
+        if cur_line != Some(line) {
+            active = [vec![], vec![]];
+            cur_line = Some(line);
+        }
+        // Everything still active started at or before lo.
+        active[1 - w].retain(|&(ohi, _)| ohi >= lo);
+        for &(ohi, j) in &active[1 - w] {
+            let (ia, ib) = if w == 0 { (i, j) } else { (j, i) };
+            for t in lo..=hi.min(ohi) {
+                let p = at(line, t);
+                out.push(Crossing { at: p, steps: (a[ia].steps_to(p).unwrap(), b[ib].steps_to(p).unwrap()) });
+            }
+        }
+        active[w].push((hi, i));
+    }
+}
+
+// In counterclockwise order, like Dir::ALL, so that turning is just
+// arithmetic on the index.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dir8 {
+    Rt,
+    UpRt,
+    Up,
+    UpLf,
+    Lf,
+    DnLf,
+    Dn,
+    DnRt,
+}
+
+impl Dir8 {
+    pub const ALL: [Dir8; 8] = [Dir8::Rt, Dir8::UpRt, Dir8::Up, Dir8::UpLf,
+                                Dir8::Lf, Dir8::DnLf, Dir8::Dn, Dir8::DnRt];
+
+    pub fn to_move(self) -> Move {
+        let (dx, dy) = match self {
+            Dir8::Rt => (1, 0),
+            Dir8::UpRt => (1, 1),
+            Dir8::Up => (0, 1),
+            Dir8::UpLf => (-1, 1),
+            Dir8::Lf => (-1, 0),
+            Dir8::DnLf => (-1, -1),
+            Dir8::Dn => (0, -1),
+            Dir8::DnRt => (1, -1),
+        };
+        Move { dx, dy }
+    }
+
+    pub fn from_move(m: Move) -> Option<Dir8> {
+        Dir8::ALL.iter().cloned().find(|d| d.to_move() == m)
+    }
+
+    // Counterclockwise eighth turns; negative is clockwise.
+    pub fn rotate(self, eighths: i32) -> Dir8 {
+        Dir8::ALL[(self as i32 + eighths).rem_euclid(8) as usize]
+    }
+
+    pub fn turn_left(self) -> Dir8 {
+        self.rotate(1)
+    }
+
+    pub fn turn_right(self) -> Dir8 {
+        self.rotate(-1)
+    }
+
+    pub fn rev(self) -> Dir8 {
+        self.rotate(4)
+    }
+
+    pub fn is_diagonal(self) -> bool {
+        self as usize % 2 == 1
+    }
+
+    pub fn to_dir(self) -> Option<Dir> {
+        Dir::from_move(self.to_move())
+    }
+}
+
+impl From<Dir> for Dir8 {
+    fn from(d: Dir) -> Dir8 {
+        match d {
+            Dir::Rt => Dir8::Rt,
+            Dir::Up => Dir8::Up,
+            Dir::Lf => Dir8::Lf,
+            Dir::Dn => Dir8::Dn,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Walker {
     here: Point,
@@ -200,7 +484,7 @@ impl<Path> Iterator for WalkMany<Path>
 
 #[cfg(test)]
 mod test {
-    use super::{Point, Dir, Len};
+    use super::{Point, Move, Dir, Dir8, Len, Segment, crossings};
     use ::quickcheck::*;
     use quickcheck_macros::quickcheck;
     use std::collections::HashSet;
@@ -221,6 +505,20 @@ mod test {
         }
     }
 
+    impl Arbitrary for Dir8 {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let b: usize = Arbitrary::arbitrary(g);
+            Dir8::ALL[b & 7]
+        }
+    }
+
+    impl Arbitrary for Move {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let (dx, dy): (i16, i16) = Arbitrary::arbitrary(g);
+            Move { dx: dx.into(), dy: dy.into() }
+        }
+    }
+
     fn endpt(p: Point, d: Dir, l: Len) -> Point {
         p + d.to_move() * l
     }
@@ -258,6 +556,17 @@ mod test {
         d.rev().rev() == d
     }
 
+    #[quickcheck]
+    fn qc_dir_turns(d: Dir) -> bool {
+        d.turn_left().to_move() == d.to_move().turn_left()
+            && d.turn_right().to_move() == d.to_move().turn_right()
+    }
+
+    #[quickcheck]
+    fn qc_dir_from_move(d: Dir) -> bool {
+        Dir::from_move(d.to_move()) == Some(d)
+    }
+
     #[quickcheck]
     fn qc_mov_len1(d: Dir, l: Len) -> bool {
         (d.to_move() * l).len() == l
@@ -331,4 +640,96 @@ mod test {
         assert_eq!(Dir::Up.to_move().turn_right(), Dir::Rt.to_move());
         assert_eq!(Dir::Rt.to_move().turn_right(), Dir::Dn.to_move());
     }
+
+    #[quickcheck]
+    fn qc_rotate_quarters(m: Move, n: i8) -> bool {
+        let n = i32::from(n);
+        m.rotate(n).rotate(-n) == m && m.rotate(n + 4) == m.rotate(n) && m.rotate(n).len() == m.len()
+    }
+
+    #[test]
+    fn rotate_like_turns() {
+        let m = Move { dx: 3, dy: 1 };
+        assert_eq!(m.rotate(1), m.turn_left());
+        assert_eq!(m.rotate(-1), m.turn_right());
+        assert_eq!(m.rotate(2), Move { dx: -3, dy: -1 });
+        assert_eq!(m.flip_x(), Move { dx: -3, dy: 1 });
+        assert_eq!(m.flip_y(), Move { dx: 3, dy: -1 });
+        assert_eq!(m.chebyshev_len(), 3);
+        assert_eq!(Move { dx: -2, dy: -5 }.chebyshev_len(), 5);
+    }
+
+    #[quickcheck]
+    fn qc_dir8_turns(d: Dir8) -> bool {
+        d.turn_left().turn_right() == d && d.rev().rev() == d
+            && (0..8).fold(d, |d, _| d.turn_left()) == d
+            && d.turn_left().turn_left().to_move() == d.to_move().turn_left()
+    }
+
+    #[quickcheck]
+    fn qc_dir8_moves(d: Dir8) -> bool {
+        let m = d.to_move();
+        Dir8::from_move(m) == Some(d) && m.chebyshev_len() == 1
+            && m.len() == if d.is_diagonal() { 2 } else { 1 }
+            && d.to_dir().map(Dir8::from) == if d.is_diagonal() { None } else { Some(d) }
+    }
+
+    #[test]
+    fn dir8_from_dir() {
+        for &d in &Dir::ALL {
+            assert_eq!(Dir8::from(d).to_move(), d.to_move());
+        }
+    }
+
+    #[quickcheck]
+    fn qc_neighbors(p: Point) -> bool {
+        p.neighbors4().all(|q| (q - p).len() == 1) && p.neighbors4().count() == 4
+            && is_unique(p.neighbors8()) && p.neighbors8().all(|q| (q - p).chebyshev_len() == 1)
+            && p.neighbors8().count() == 8
+    }
+
+    fn brute_crossings(a: &[(Dir, Len)], b: &[(Dir, Len)]) -> Vec<(i32, i32, Len, Len)> {
+        let pa: Vec<_> = Point::origin().walk_many(a.iter().cloned()).collect();
+        let pb: Vec<_> = Point::origin().walk_many(b.iter().cloned()).collect();
+        let mut out = vec![];
+        for (i, p) in pa.iter().enumerate() {
+            for (j, q) in pb.iter().enumerate() {
+                if p == q {
+                    out.push((p.x, p.y, i as Len + 1, j as Len + 1));
+                }
+            }
+        }
+        out.sort();
+        out
+    }
+
+    #[quickcheck]
+    fn qc_crossings_like_walking(a: Vec<(Dir, u8)>, b: Vec<(Dir, u8)>) -> bool {
+        let a: Vec<_> = a.into_iter().map(|(d, l)| (d, Len::from(l % 8))).collect();
+        let b: Vec<_> = b.into_iter().map(|(d, l)| (d, Len::from(l % 8))).collect();
+        if a.is_empty() || b.is_empty() {
+            return true;
+        }
+        let sa = Segment::path(Point::origin(), a.iter().cloned());
+        let sb = Segment::path(Point::origin(), b.iter().cloned());
+        let mut obs: Vec<_> = crossings(&sa, &sb).into_iter()
+            .map(|c| (c.at.x, c.at.y, c.steps.0, c.steps.1)).collect();
+        obs.sort();
+        obs == brute_crossings(&a, &b)
+    }
+
+    #[test]
+    fn segment_basics() {
+        let path = Segment::path(Point::origin(), vec![(Dir::Rt, 8), (Dir::Up, 5), (Dir::Lf, 5)]);
+        assert_eq!(path[2], Segment { start: Point { x: 8, y: 5 }, dir: Dir::Lf, len: 5, steps: 13 });
+        assert_eq!(path[2].end(), Point { x: 3, y: 5 });
+        assert_eq!(path[0].span(), Some((Point { x: 1, y: 0 }, Point { x: 8, y: 0 })));
+        assert!(!path[1].contains(Point { x: 8, y: 0 }));
+        assert_eq!(path[2].steps_to(Point { x: 6, y: 5 }), Some(15));
+        let up = Segment::new(Point { x: 6, y: -2 }, Dir::Up, 9);
+        assert_eq!(path[2].intersection(up), Some((Point { x: 6, y: 5 }, Point { x: 6, y: 5 })));
+        assert_eq!(path[0].intersection(Segment::new(Point::origin(), Dir::Rt, 3)),
+                   Some((Point { x: 1, y: 0 }, Point { x: 3, y: 0 })));
+        assert_eq!(path[0].intersection(path[1]), None);
+    }
 }
@@ -0,0 +1,210 @@
+use std::ops::{Index, IndexMut};
+
+use crate::geom::{Coord, Point};
+
+// Row y is the y'th line of the text it was parsed from, so unlike the
+// rest of this crate, y increases downwards.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GridError {
+    Ragged { line: usize, len: usize, width: usize },
+}
+
+impl<T> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Self
+        where T: Clone
+    {
+        Self { width, height, cells: vec![fill; width * height] }
+    }
+
+    pub fn from_vec(width: usize, cells: Vec<T>) -> Self {
+        assert!(width > 0 && cells.len().is_multiple_of(width), "{} cells don't fill rows of {}", cells.len(), width);
+        Self { width, height: cells.len() / width, cells }
+    }
+
+    // Trailing blank lines are ignored; any other line must be as long
+    // as the first.
+    pub fn from_ascii<I, F>(lines: I, mut f: F) -> Result<Self, GridError>
+        where I: IntoIterator,
+              I::Item: AsRef<str>,
+              F: FnMut(char) -> T
+    {
+        let mut width = None;
+        let mut height = 0;
+        let mut blank = 0;
+        let mut cells = vec![];
+        for (y, line) in lines.into_iter().enumerate() {
+            let line = line.as_ref();
+            if line.is_empty() {
+                blank += 1;
+                continue;
+            }
+            let len = line.chars().count();
+            let width = *width.get_or_insert(len);
+            if len != width || blank > 0 {
+                return Err(GridError::Ragged { line: y - blank, len: if blank > 0 { 0 } else { len }, width });
+            }
+            cells.extend(line.chars().map(&mut f));
+            height += 1;
+        }
+        Ok(Self { width: width.unwrap_or(0), height, cells })
+    }
+
+    pub fn to_ascii<F>(&self, mut f: F) -> String
+        where F: FnMut(&T) -> char
+    {
+        let mut s = String::with_capacity((self.width + 1) * self.height);
+        for row in self.rows() {
+            s.extend(row.iter().map(&mut f));
+            s.push('\n');
+        }
+        s
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // For consistency with the sparse types; this is empty (high < low)
+    // if the grid is.
+    pub fn bounding_box(&self) -> (Point, Point) {
+        (Point::origin(), Point { x: self.width as Coord - 1, y: self.height as Coord - 1 })
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        p.x >= 0 && p.y >= 0 && (p.x as usize) < self.width && (p.y as usize) < self.height
+    }
+
+    fn offset(&self, p: Point) -> Option<usize> {
+        if self.contains(p) {
+            Some(p.y as usize * self.width + p.x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, p: Point) -> Option<&T> {
+        self.offset(p).map(move |i| &self.cells[i])
+    }
+
+    pub fn get_mut(&mut self, p: Point) -> Option<&mut T> {
+        let i = self.offset(p)?;
+        Some(&mut self.cells[i])
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> + '_ {
+        // chunks() would panic on a zero width.
+        (0..self.height).map(move |y| self.row(y))
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> + '_ {
+        assert!(x < self.width);
+        self.cells[x..].iter().step_by(self.width)
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T> + '_> + '_ {
+        (0..self.width).map(move |x| self.column(x))
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        let width = self.width;
+        (0..self.cells.len()).map(move |i| Point { x: (i % width) as Coord, y: (i / width) as Coord })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> + '_ {
+        self.points().zip(self.cells.iter())
+    }
+
+    // Orthogonal neighbors that are inside the grid.
+    pub fn neighbors4(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        p.neighbors4().filter(move |&q| self.contains(q))
+    }
+
+    // Orthogonal and diagonal neighbors that are inside the grid.
+    pub fn neighbors8(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        p.neighbors8().filter(move |&q| self.contains(q))
+    }
+}
+
+impl<T> Index<Point> for Grid<T> {
+    type Output = T;
+    fn index(&self, p: Point) -> &T {
+        self.get(p).unwrap_or_else(|| panic!("{:?} outside {}x{} grid", p, self.width, self.height))
+    }
+}
+
+impl<T> IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, p: Point) -> &mut T {
+        let (width, height) = (self.width, self.height);
+        self.get_mut(p).unwrap_or_else(|| panic!("{:?} outside {}x{} grid", p, width, height))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Grid, GridError};
+    use crate::geom::Point;
+
+    const ART: &[&str] = &["#..", ".#.", "##.", ""];
+
+    fn pt(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+
+    #[test]
+    fn ascii_roundtrip() {
+        let g = Grid::from_ascii(ART, |ch| ch == '#').unwrap();
+        assert_eq!((g.width(), g.height()), (3, 3));
+        assert!(g[pt(0, 0)] && g[pt(1, 1)] && !g[pt(2, 2)]);
+        assert_eq!(g.to_ascii(|&b| if b { '#' } else { '.' }), "#..\n.#.\n##.\n");
+    }
+
+    #[test]
+    fn ragged() {
+        assert_eq!(Grid::from_ascii(&["ab", "abc"], |ch| ch),
+                   Err(GridError::Ragged { line: 1, len: 3, width: 2 }));
+        assert_eq!(Grid::from_ascii(&["ab", "", "ab"], |ch| ch),
+                   Err(GridError::Ragged { line: 1, len: 0, width: 2 }));
+    }
+
+    #[test]
+    fn rows_and_columns() {
+        let g = Grid::from_vec(3, (0..6).collect());
+        assert_eq!(g.rows().collect::<Vec<_>>(), vec![&[0, 1, 2][..], &[3, 4, 5][..]]);
+        assert_eq!(g.column(1).cloned().collect::<Vec<_>>(), vec![1, 4]);
+        assert_eq!(g.columns().count(), 3);
+        assert_eq!(g.iter().nth(4), Some((pt(1, 1), &4)));
+        assert_eq!(g.get(pt(3, 0)), None);
+        assert_eq!(g.get(pt(0, -1)), None);
+    }
+
+    #[test]
+    fn neighbors() {
+        let g = Grid::new(3, 2, ());
+        assert_eq!(g.neighbors4(pt(0, 0)).collect::<Vec<_>>(), vec![pt(1, 0), pt(0, 1)]);
+        assert_eq!(g.neighbors4(pt(1, 1)).count(), 3);
+        assert_eq!(g.neighbors8(pt(0, 0)).collect::<Vec<_>>(), vec![pt(1, 0), pt(1, 1), pt(0, 1)]);
+        assert_eq!(g.neighbors8(pt(1, 0)).count(), 5);
+    }
+
+    #[test]
+    fn index_mut() {
+        let mut g = Grid::new(2, 2, 0);
+        g[pt(1, 0)] = 5;
+        assert_eq!(g.to_ascii(|&n| (b'0' + n) as char), "05\n00\n");
+    }
+}
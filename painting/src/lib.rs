@@ -1,5 +1,14 @@
 pub mod geom;
+pub mod grid;
+pub mod ocr;
+pub mod point_map;
 pub mod point_set;
+pub mod render;
+pub mod search;
+pub mod turtle;
 
-pub use geom::{Len,Coord,Point,Move,Dir};
+pub use geom::{Len,Coord,Point,Move,Dir,Dir8,Segment};
+pub use grid::Grid;
+pub use point_map::PointMap;
 pub use point_set::PointSet;
+pub use turtle::Turtle;
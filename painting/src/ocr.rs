@@ -0,0 +1,191 @@
+use std::fmt;
+
+use crate::geom::Point;
+use crate::point_set::PointSet;
+use crate::render::{View, YAxis};
+
+// The block letters Advent of Code prints, with one blank column (or
+// more) between them; 4x6 is the usual size, and 6x10 is the larger
+// font from 2018.  Not every letter appears in either.
+const FONT_6: &[(char, [&str; 6])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+const FONT_10: &[(char, [&str; 10])] = &[
+    ('A', ["..##..", ".#..#.", "#....#", "#....#", "#....#",
+           "######", "#....#", "#....#", "#....#", "#....#"]),
+    ('B', ["#####.", "#....#", "#....#", "#....#", "#####.",
+           "#....#", "#....#", "#....#", "#....#", "#####."]),
+    ('C', [".####.", "#....#", "#.....", "#.....", "#.....",
+           "#.....", "#.....", "#.....", "#....#", ".####."]),
+    ('E', ["######", "#.....", "#.....", "#.....", "#####.",
+           "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('F', ["######", "#.....", "#.....", "#.....", "#####.",
+           "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('G', [".####.", "#....#", "#.....", "#.....", "#.....",
+           "#..###", "#....#", "#....#", "#...##", ".###.#"]),
+    ('H', ["#....#", "#....#", "#....#", "#....#", "######",
+           "#....#", "#....#", "#....#", "#....#", "#....#"]),
+    ('J', ["...###", "....#.", "....#.", "....#.", "....#.",
+           "....#.", "....#.", "#...#.", "#...#.", ".###.."]),
+    ('K', ["#....#", "#...#.", "#..#..", "#.#...", "##....",
+           "##....", "#.#...", "#..#..", "#...#.", "#....#"]),
+    ('L', ["#.....", "#.....", "#.....", "#.....", "#.....",
+           "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('N', ["#....#", "##...#", "##...#", "#.#..#", "#.#..#",
+           "#..#.#", "#..#.#", "#...##", "#...##", "#....#"]),
+    ('P', ["#####.", "#....#", "#....#", "#....#", "#####.",
+           "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('R', ["#####.", "#....#", "#....#", "#....#", "#####.",
+           "#..#..", "#...#.", "#...#.", "#....#", "#....#"]),
+    ('X', ["#....#", "#....#", ".#..#.", ".#..#.", "..##..",
+           "..##..", ".#..#.", ".#..#.", "#....#", "#....#"]),
+    ('Z', ["######", ".....#", ".....#", "....#.", "...#..",
+           "..#...", ".#....", "#.....", "#.....", "######"]),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OcrError {
+    // After trimming blank rows, the text wasn't 6 or 10 rows high.
+    Height(usize),
+    // The index'th glyph (counting from 0) isn't a letter in the font;
+    // the glyph is drawn as rows of '#' and '.'.
+    Unknown { index: usize, glyph: String },
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OcrError::Height(h) => write!(f, "text is {} rows high, not 6 or 10", h),
+            OcrError::Unknown { index, glyph } => write!(f, "unknown glyph at {}:\n{}", index, glyph),
+        }
+    }
+}
+
+// Removes blank columns from both sides of a glyph.
+fn trim_columns(rows: &[Vec<bool>]) -> Vec<String> {
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let used: Vec<usize> = (0..width).filter(|&x| rows.iter().any(|r| r[x])).collect();
+    let (lo, hi) = match (used.first(), used.last()) {
+        (Some(&lo), Some(&hi)) => (lo, hi),
+        _ => return rows.iter().map(|_| String::new()).collect(),
+    };
+    rows.iter().map(|r| r[lo..=hi].iter().map(|&b| if b { '#' } else { '.' }).collect()).collect()
+}
+
+fn lookup<R: AsRef<[&'static str]>>(font: &[(char, R)], glyph: &[String]) -> Option<char> {
+    font.iter().find(|(_, art)| {
+        let art: Vec<Vec<bool>> = art.as_ref().iter().map(|r| r.chars().map(|c| c == '#').collect()).collect();
+        trim_columns(&art) == glyph
+    }).map(|&(ch, _)| ch)
+}
+
+pub fn recognize<F>(view: &View, mut lit: F) -> Result<String, OcrError>
+    where F: FnMut(Point) -> bool
+{
+    let mut rows: Vec<Vec<bool>> = view.rows().map(|row| row.map(&mut lit).collect()).collect();
+    while rows.last().is_some_and(|r| !r.contains(&true)) {
+        rows.pop();
+    }
+    let top = rows.iter().position(|r| r.contains(&true)).unwrap_or(rows.len());
+    rows.drain(..top);
+    if rows.len() != 6 && rows.len() != 10 {
+        return Err(OcrError::Height(rows.len()));
+    }
+
+    let width = view.width();
+    let blank: Vec<bool> = (0..width).map(|x| rows.iter().all(|r| !r[x])).collect();
+    let mut text = String::new();
+    let mut x = 0;
+    while x < width {
+        if blank[x] {
+            x += 1;
+            continue;
+        }
+        let end = (x..width).find(|&e| blank[e]).unwrap_or(width);
+        let cols: Vec<Vec<bool>> = rows.iter().map(|r| r[x..end].to_vec()).collect();
+        let glyph = trim_columns(&cols);
+        let ch = if rows.len() == 6 { lookup(FONT_6, &glyph) } else { lookup(FONT_10, &glyph) };
+        match ch {
+            Some(ch) => text.push(ch),
+            None => return Err(OcrError::Unknown { index: text.len(), glyph: glyph.join("\n") }),
+        }
+        x = end;
+    }
+    Ok(text)
+}
+
+pub fn recognize_set(ps: &PointSet, y_axis: YAxis) -> Result<String, OcrError> {
+    recognize(&View::new(ps.bounding_box(), y_axis), |p| ps.contains(p))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grid::Grid;
+
+    fn set_of(lines: &[&str]) -> PointSet {
+        let grid = Grid::from_ascii(lines, |ch| ch == '#').unwrap();
+        grid.iter().filter(|&(_p, &b)| b).map(|(p, _b)| p).collect()
+    }
+
+    #[test]
+    fn small_font() {
+        let ps = set_of(&["###..#..#..##..", "#..#.#..#.#..#.", "#..#.####.#....",
+                          "###..#..#.#.##.", "#.#..#..#.#..#.", "#..#.#..#..###."]);
+        assert_eq!(recognize_set(&ps, YAxis::Down), Ok("RHG".to_owned()));
+    }
+
+    #[test]
+    fn every_letter() {
+        for &(ch, ref art) in FONT_6 {
+            assert_eq!(recognize_set(&set_of(art), YAxis::Down), Ok(ch.to_string()));
+        }
+        for &(ch, ref art) in FONT_10 {
+            assert_eq!(recognize_set(&set_of(art), YAxis::Down), Ok(ch.to_string()));
+        }
+    }
+
+    #[test]
+    fn y_up() {
+        // Day 11 draws with y increasing upwards, and from y=0 down.
+        let ps: PointSet = set_of(&["#...", "#...", "#...", "#...", "#...", "####"])
+            .iter().map(|p| Point { x: p.x, y: -p.y }).collect();
+        assert_eq!(recognize_set(&ps, YAxis::Up), Ok("L".to_owned()));
+        assert!(recognize_set(&ps, YAxis::Down).is_err());
+    }
+
+    #[test]
+    fn large_font() {
+        let art: Vec<String> = FONT_10[10].1.iter().zip(FONT_10[14].1.iter())
+            .map(|(n, z)| format!("..{}..{}", n, z)).collect();
+        let grid = Grid::from_ascii(&art, |ch| ch == '#').unwrap();
+        assert_eq!(recognize(&View::new(grid.bounding_box(), YAxis::Down), |p| grid[p]),
+                   Ok("NZ".to_owned()));
+    }
+
+    #[test]
+    fn errors() {
+        let ps = set_of(&["#..#.#", "#..#.#", "####.#", "#..#.#", "#..#.#", "#..#.#"]);
+        assert_eq!(recognize_set(&ps, YAxis::Down),
+                   Err(OcrError::Unknown { index: 1, glyph: "#\n#\n#\n#\n#\n#".to_owned() }));
+        assert_eq!(recognize_set(&set_of(&["##"]), YAxis::Down), Err(OcrError::Height(1)));
+    }
+}
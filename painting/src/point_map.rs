@@ -0,0 +1,253 @@
+use std::collections::{hash_map, HashMap};
+use std::iter::FromIterator;
+use std::ops::Index;
+
+use crate::geom::{Dir, Point};
+use crate::point_set::{BitSet, split, join, tiles_bounds};
+
+// Same 8x8 tiling as PointSet; the mask says which cells are occupied.
+#[derive(Clone, Debug)]
+struct Tile<T> {
+    mask: BitSet,
+    cells: Box<[Option<T>]>,
+}
+
+impl<T> Tile<T> {
+    fn new() -> Self {
+        Self { mask: 0, cells: (0..64).map(|_| None).collect() }
+    }
+}
+
+fn index(mask: BitSet) -> usize {
+    mask.trailing_zeros() as usize
+}
+
+#[derive(Clone, Debug)]
+pub struct PointMap<T> {
+    inner: HashMap<Point, Tile<T>>,
+}
+
+impl<T> PointMap<T> {
+    pub fn new() -> Self {
+        Self { inner: HashMap::new() }
+    }
+
+    pub fn contains_key(&self, p: Point) -> bool {
+        let (key, mask) = split(p);
+        self.inner.get(&key).map(|tile| tile.mask).unwrap_or(0) & mask != 0
+    }
+
+    pub fn get(&self, p: Point) -> Option<&T> {
+        let (key, mask) = split(p);
+        self.inner.get(&key)?.cells[index(mask)].as_ref()
+    }
+
+    pub fn get_mut(&mut self, p: Point) -> Option<&mut T> {
+        let (key, mask) = split(p);
+        self.inner.get_mut(&key)?.cells[index(mask)].as_mut()
+    }
+
+    pub fn insert(&mut self, p: Point, val: T) -> Option<T> {
+        let (key, mask) = split(p);
+        let tile = self.inner.entry(key).or_insert_with(Tile::new);
+        tile.mask |= mask;
+        tile.cells[index(mask)].replace(val)
+    }
+
+    pub fn remove(&mut self, p: Point) -> Option<T> {
+        let (key, mask) = split(p);
+        let tile = self.inner.get_mut(&key)?;
+        let old = tile.cells[index(mask)].take();
+        tile.mask &= !mask;
+        if tile.mask == 0 {
+            self.inner.remove(&key);
+        }
+        old
+    }
+
+    pub fn entry(&mut self, p: Point) -> Entry<'_, T> {
+        Entry { map: self, p }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.values().map(|tile| tile.mask.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn bounding_box(&self) -> (Point, Point) {
+        if self.inner.is_empty() {
+            return (Point::origin(), Point::origin());
+        }
+        tiles_bounds(self.inner.iter().map(|(&key, tile)| (key, tile.mask)))
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { tiles: self.inner.iter(), cur: None }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = Point> + '_ {
+        self.iter().map(|(p, _)| p)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.iter().map(|(_, v)| v)
+    }
+
+    // The occupied orthogonal neighbors of a point, with the direction
+    // to each.
+    pub fn neighbors(&self, p: Point) -> impl Iterator<Item = (Dir, Point, &T)> + '_ {
+        Dir::ALL.iter().filter_map(move |&dir| {
+            let q = p + dir.to_move();
+            self.get(q).map(|v| (dir, q, v))
+        })
+    }
+}
+
+impl<T> Default for PointMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Index<Point> for PointMap<T> {
+    type Output = T;
+    fn index(&self, p: Point) -> &T {
+        self.get(p).expect("no entry for point")
+    }
+}
+
+pub struct Entry<'a, T> {
+    map: &'a mut PointMap<T>,
+    p: Point,
+}
+
+impl<'a, T> Entry<'a, T> {
+    pub fn key(&self) -> Point {
+        self.p
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> T>(self, f: F) -> &'a mut T {
+        if !self.map.contains_key(self.p) {
+            self.map.insert(self.p, f());
+        }
+        self.map.get_mut(self.p).unwrap()
+    }
+
+    pub fn or_insert(self, val: T) -> &'a mut T {
+        self.or_insert_with(|| val)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut T)>(self, f: F) -> Self {
+        if let Some(v) = self.map.get_mut(self.p) {
+            f(v);
+        }
+        self
+    }
+}
+
+impl<'a, T: Default> Entry<'a, T> {
+    pub fn or_default(self) -> &'a mut T {
+        self.or_insert_with(T::default)
+    }
+}
+
+pub struct Iter<'a, T> {
+    tiles: hash_map::Iter<'a, Point, Tile<T>>,
+    cur: Option<(Point, BitSet, &'a [Option<T>])>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Point, &'a T);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, ref mut bits, cells)) = self.cur {
+                if *bits != 0 {
+                    let b = bits.trailing_zeros();
+                    *bits &= *bits - 1;
+                    return Some((join(key, b), cells[b as usize].as_ref().unwrap()));
+                }
+            }
+            let (&key, tile) = self.tiles.next()?;
+            self.cur = Some((key, tile.mask, &tile.cells));
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a PointMap<T> {
+    type Item = (Point, &'a T);
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> FromIterator<(Point, T)> for PointMap<T> {
+    fn from_iter<I: IntoIterator<Item = (Point, T)>>(iter: I) -> Self {
+        let mut pm = PointMap::new();
+        pm.extend(iter);
+        pm
+    }
+}
+
+impl<T> Extend<(Point, T)> for PointMap<T> {
+    fn extend<I: IntoIterator<Item = (Point, T)>>(&mut self, iter: I) {
+        for (p, v) in iter {
+            self.insert(p, v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PointMap;
+    use crate::geom::{Dir, Point};
+    use crate::point_set::PointSet;
+    use quickcheck_macros::quickcheck;
+    use std::collections::HashMap;
+
+    #[quickcheck]
+    fn qc_like_hashmap(ops: Vec<(Point, Option<u8>)>) -> bool {
+        let mut pm = PointMap::new();
+        let mut hm = HashMap::new();
+        for (p, op) in ops {
+            let ok = match op {
+                Some(v) => pm.insert(p, v) == hm.insert(p, v),
+                None => pm.remove(p) == hm.remove(&p),
+            };
+            if !ok || pm.len() != hm.len() {
+                return false;
+            }
+        }
+        pm.iter().map(|(p, &v)| (p, v)).collect::<HashMap<_, _>>() == hm
+    }
+
+    #[quickcheck]
+    fn qc_bbox_like_set(pv: Vec<Point>) -> bool {
+        let pm: PointMap<()> = pv.iter().map(|&p| (p, ())).collect();
+        let ps: PointSet = pv.into_iter().collect();
+        pm.bounding_box() == ps.bounding_box() && pm.len() == ps.len()
+    }
+
+    #[test]
+    fn entry() {
+        let mut pm: PointMap<u32> = PointMap::new();
+        let p = Point { x: -3, y: 12 };
+        *pm.entry(p).or_default() += 2;
+        pm.entry(p).and_modify(|v| *v *= 10).or_insert(7);
+        pm.entry(Point::origin()).and_modify(|v| *v *= 10).or_insert(7);
+        assert_eq!(pm[p], 20);
+        assert_eq!(pm.get(Point::origin()), Some(&7));
+        assert_eq!(pm.len(), 2);
+    }
+
+    #[test]
+    fn neighbors() {
+        let pm: PointMap<char> = vec![(Point { x: 1, y: 0 }, 'a'), (Point { x: 0, y: -1 }, 'b'),
+                                      (Point { x: 1, y: 1 }, 'c')].into_iter().collect();
+        let ns: Vec<_> = pm.neighbors(Point::origin()).collect();
+        assert_eq!(ns, vec![(Dir::Rt, Point { x: 1, y: 0 }, &'a'), (Dir::Dn, Point { x: 0, y: -1 }, &'b')]);
+    }
+}
@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{hash_map, HashMap};
+use std::iter::FromIterator;
+use std::ops::{BitAnd, BitOr, BitXor, Sub};
 
-use crate::geom::Point;
+use crate::geom::{Coord, Point};
 
-type BitSet = u64;
+pub(crate) type BitSet = u64;
 
-fn split(p: Point) -> (Point, BitSet) {
+pub(crate) fn split(p: Point) -> (Point, BitSet) {
     let xh = p.x >> 3;
     let yh = p.y >> 3;
     let xl = p.x & 7;
@@ -13,6 +15,28 @@ fn split(p: Point) -> (Point, BitSet) {
     (Point { x: xh, y: yh }, 1 << b)
 }
 
+pub(crate) fn join(key: Point, b: u32) -> Point {
+    Point { x: (key.x << 3) + (b & 7) as i32, y: (key.y << 3) + (b >> 3) as i32 }
+}
+
+// The exact bounds of the set bits in a collection of nonempty tiles.
+pub(crate) fn tiles_bounds<I>(tiles: I) -> (Point, Point)
+    where I: IntoIterator<Item = (Point, BitSet)>
+{
+    let mut low = Point { x: Coord::MAX, y: Coord::MAX };
+    let mut high = Point { x: Coord::MIN, y: Coord::MIN };
+    for (key, tile) in tiles {
+        // Rows are bytes, so row bounds come from the bit scans
+        // directly; for columns, OR the rows together first.
+        let cols = (0..8).fold(0, |acc, row| acc | (tile >> (row * 8)) as u8);
+        let tl = join(key, tile.trailing_zeros() & !7 | cols.trailing_zeros());
+        let th = join(key, (63 - tile.leading_zeros()) & !7 | (7 - cols.leading_zeros()));
+        low = Point { x: low.x.min(tl.x), y: low.y.min(tl.y) };
+        high = Point { x: high.x.max(th.x), y: high.y.max(th.y) };
+    }
+    (low, high)
+}
+
 #[derive(Clone, Debug)]
 pub struct PointSet {
     inner: HashMap<Point, BitSet>
@@ -38,10 +62,16 @@ impl PointSet {
 
     pub fn remove(&mut self, p: Point) -> bool {
         let (key, mask) = split(p);
-        let tile_ptr = self.inner.entry(key).or_insert(0);
-        let old = *tile_ptr & mask != 0;
-        *tile_ptr &= !mask;
-        return old;
+        let tile = match self.inner.get_mut(&key) {
+            Some(tile) => tile,
+            None => return false,
+        };
+        let old = *tile & mask != 0;
+        *tile &= !mask;
+        if *tile == 0 {
+            self.inner.remove(&key);
+        }
+        old
     }
 
     pub fn len(&self) -> usize {
@@ -52,11 +82,120 @@ impl PointSet {
         if self.inner.is_empty() {
             return (Point::origin(), Point::origin());
         }
-        let xl = self.inner.keys().map(|p| p.x * 8).min().unwrap();
-        let yl = self.inner.keys().map(|p| p.y * 8).min().unwrap();
-        let xh = self.inner.keys().map(|p| p.x * 8 + 7).max().unwrap();
-        let yh = self.inner.keys().map(|p| p.y * 8 + 7).max().unwrap();
-        (Point { x: xl, y: yl }, Point { x: xh, y: yh })
+        tiles_bounds(self.inner.iter().map(|(&key, &tile)| (key, tile)))
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { tiles: self.inner.iter(), key: Point::origin(), bits: 0 }
+    }
+
+    // The set operations work a tile (64 points) at a time.
+    fn combine<F>(&self, other: &Self, both: bool, f: F) -> Self
+        where F: Fn(BitSet, BitSet) -> BitSet
+    {
+        let mut inner = HashMap::new();
+        for (&key, &tile) in &self.inner {
+            let bits = f(tile, other.inner.get(&key).cloned().unwrap_or(0));
+            if bits != 0 {
+                inner.insert(key, bits);
+            }
+        }
+        if both {
+            for (&key, &tile) in &other.inner {
+                if !self.inner.contains_key(&key) && f(0, tile) != 0 {
+                    inner.insert(key, f(0, tile));
+                }
+            }
+        }
+        Self { inner }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.combine(other, true, |a, b| a | b)
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.combine(other, false, |a, b| a & b)
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        self.combine(other, false, |a, b| a & !b)
+    }
+
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.combine(other, true, |a, b| a ^ b)
+    }
+}
+
+pub struct Iter<'a> {
+    tiles: hash_map::Iter<'a, Point, BitSet>,
+    key: Point,
+    bits: BitSet,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Point;
+    fn next(&mut self) -> Option<Point> {
+        while self.bits == 0 {
+            let (&key, &bits) = self.tiles.next()?;
+            self.key = key;
+            self.bits = bits;
+        }
+        let b = self.bits.trailing_zeros();
+        self.bits &= self.bits - 1;
+        Some(join(self.key, b))
+    }
+}
+
+impl<'a> IntoIterator for &'a PointSet {
+    type Item = Point;
+    type IntoIter = Iter<'a>;
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl FromIterator<Point> for PointSet {
+    fn from_iter<I: IntoIterator<Item = Point>>(iter: I) -> Self {
+        let mut ps = PointSet::new();
+        ps.extend(iter);
+        ps
+    }
+}
+
+impl Extend<Point> for PointSet {
+    fn extend<I: IntoIterator<Item = Point>>(&mut self, iter: I) {
+        for p in iter {
+            self.insert(p);
+        }
+    }
+}
+
+impl BitOr for &PointSet {
+    type Output = PointSet;
+    fn bitor(self, other: &PointSet) -> PointSet {
+        self.union(other)
+    }
+}
+
+impl BitAnd for &PointSet {
+    type Output = PointSet;
+    fn bitand(self, other: &PointSet) -> PointSet {
+        self.intersection(other)
+    }
+}
+
+impl Sub for &PointSet {
+    type Output = PointSet;
+    fn sub(self, other: &PointSet) -> PointSet {
+        self.difference(other)
+    }
+}
+
+impl BitXor for &PointSet {
+    type Output = PointSet;
+    fn bitxor(self, other: &PointSet) -> PointSet {
+        self.symmetric_difference(other)
     }
 }
 
@@ -65,6 +204,11 @@ mod test {
     use super::PointSet;
     use crate::geom::Point;
     use quickcheck_macros::quickcheck;
+    use std::collections::HashSet;
+
+    fn to_hash(ps: &PointSet) -> HashSet<Point> {
+        ps.iter().collect()
+    }
 
     #[quickcheck]
     fn qc_empty_contains_nothing(p: Point) -> bool {
@@ -200,4 +344,61 @@ mod test {
         let (low, high) = ps.bounding_box();
         pv.iter().all(|p| low.x <= p.x && low.y <= p.y && high.x >= p.x && high.y >= p.y)
     }
+
+    #[quickcheck]
+    fn qc_iter_roundtrip(pv: Vec<Point>) -> bool {
+        let ps: PointSet = pv.iter().cloned().collect();
+        let v: Vec<_> = ps.iter().collect();
+        v.len() == ps.len() && to_hash(&ps) == pv.into_iter().collect()
+    }
+
+    #[test]
+    fn iter_tile_edges() {
+        let pv = vec![Point { x: -1, y: -1 }, Point { x: 7, y: 0 }, Point { x: 0, y: 7 },
+                      Point { x: -8, y: 8 }, Point { x: i32::MAX, y: i32::MIN }];
+        let ps: PointSet = pv.iter().cloned().collect();
+        assert_eq!(to_hash(&ps), pv.into_iter().collect());
+    }
+
+    #[quickcheck]
+    fn qc_set_algebra(pv: Vec<Point>, qv: Vec<Point>) -> bool {
+        let (ps, qs): (PointSet, PointSet) = (pv.iter().cloned().collect(), qv.iter().cloned().collect());
+        let (ph, qh) = (to_hash(&ps), to_hash(&qs));
+        to_hash(&(&ps | &qs)) == ph.union(&qh).cloned().collect()
+            && to_hash(&(&ps & &qs)) == ph.intersection(&qh).cloned().collect()
+            && to_hash(&(&ps - &qs)) == ph.difference(&qh).cloned().collect()
+            && to_hash(&(&ps ^ &qs)) == ph.symmetric_difference(&qh).cloned().collect()
+    }
+
+    #[quickcheck]
+    fn qc_extend(pv: Vec<Point>, qv: Vec<Point>) -> bool {
+        let mut ps: PointSet = pv.iter().cloned().collect();
+        ps.extend(qv.iter().cloned());
+        to_hash(&ps) == pv.into_iter().chain(qv).collect()
+    }
+
+    #[quickcheck]
+    fn qc_bbox_tight(pv: Vec<Point>) -> bool {
+        let ps: PointSet = pv.iter().cloned().collect();
+        let (low, high) = ps.bounding_box();
+        pv.is_empty()
+            || (pv.iter().any(|p| p.x == low.x) && pv.iter().any(|p| p.y == low.y)
+                && pv.iter().any(|p| p.x == high.x) && pv.iter().any(|p| p.y == high.y))
+    }
+
+    #[test]
+    fn bbox_exact() {
+        let ps: PointSet = vec![Point { x: 3, y: -2 }, Point { x: 9, y: 5 }, Point { x: -1, y: 1 }]
+            .into_iter().collect();
+        assert_eq!(ps.bounding_box(), (Point { x: -1, y: -2 }, Point { x: 9, y: 5 }));
+    }
+
+    #[quickcheck]
+    fn qc_bbox_after_remove(p: Point, q: Point) -> bool {
+        let mut ps = PointSet::new();
+        ps.insert(p);
+        ps.insert(q);
+        ps.remove(q);
+        ps.bounding_box() == if p == q { (Point::origin(), Point::origin()) } else { (p, p) }
+    }
 }
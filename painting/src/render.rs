@@ -0,0 +1,171 @@
+use std::io::{self, Write};
+
+use crate::geom::{Coord, Point};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum YAxis {
+    // The top row is the highest y, as with Dir::Up.
+    Up,
+    // The top row is the lowest y, as in a Grid or a screen.
+    Down,
+}
+
+// A rectangle of points, inclusive of both corners (as returned by
+// bounding_box), and which way up to draw it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct View {
+    low: Point,
+    high: Point,
+    y_axis: YAxis,
+}
+
+impl View {
+    pub fn new((low, high): (Point, Point), y_axis: YAxis) -> Self {
+        Self { low, high, y_axis }
+    }
+
+    pub fn width(&self) -> usize {
+        (self.high.x - self.low.x + 1).max(0) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        (self.high.y - self.low.y + 1).max(0) as usize
+    }
+
+    // Rows from top to bottom, each from left to right.
+    pub fn rows(&self) -> impl Iterator<Item = impl Iterator<Item = Point>> {
+        let (low, high, y_axis) = (self.low, self.high, self.y_axis);
+        (0..self.height() as Coord).map(move |i| {
+            let y = match y_axis {
+                YAxis::Up => high.y - i,
+                YAxis::Down => low.y + i,
+            };
+            (low.x..=high.x).map(move |x| Point { x, y })
+        })
+    }
+
+    pub fn to_text<F>(&self, mut palette: F) -> String
+        where F: FnMut(Point) -> char
+    {
+        let mut s = String::new();
+        for row in self.rows() {
+            s.extend(row.map(&mut palette));
+            s.push('\n');
+        }
+        s
+    }
+
+    // Binary PBM, where true is black.
+    pub fn write_pbm<W, F>(&self, mut out: W, mut palette: F) -> io::Result<()>
+        where W: Write, F: FnMut(Point) -> bool
+    {
+        write!(out, "P4\n{} {}\n", self.width(), self.height())?;
+        for row in self.rows() {
+            let mut bytes = vec![0u8; self.width().div_ceil(8)];
+            for (i, p) in row.enumerate() {
+                if palette(p) {
+                    bytes[i / 8] |= 0x80 >> (i % 8);
+                }
+            }
+            out.write_all(&bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_pgm<W, F>(&self, mut out: W, mut palette: F) -> io::Result<()>
+        where W: Write, F: FnMut(Point) -> u8
+    {
+        write!(out, "P5\n{} {}\n255\n", self.width(), self.height())?;
+        for row in self.rows() {
+            out.write_all(&row.map(&mut palette).collect::<Vec<_>>())?;
+        }
+        Ok(())
+    }
+
+    pub fn write_ppm<W, F>(&self, mut out: W, mut palette: F) -> io::Result<()>
+        where W: Write, F: FnMut(Point) -> [u8; 3]
+    {
+        write!(out, "P6\n{} {}\n255\n", self.width(), self.height())?;
+        for row in self.rows() {
+            out.write_all(&row.flat_map(|p| palette(p).to_vec()).collect::<Vec<_>>())?;
+        }
+        Ok(())
+    }
+
+    // Two rows per line of text: the foreground colour of an upper half
+    // block is the top one, and the background is the bottom one.
+    pub fn write_ansi<W, F>(&self, mut out: W, mut palette: F) -> io::Result<()>
+        where W: Write, F: FnMut(Point) -> [u8; 3]
+    {
+        let rows: Vec<Vec<Point>> = self.rows().map(|row| row.collect()).collect();
+        for pair in rows.chunks(2) {
+            for (i, &p) in pair[0].iter().enumerate() {
+                let [r, g, b] = palette(p);
+                write!(out, "\x1b[38;2;{};{};{}m", r, g, b)?;
+                if let Some(below) = pair.get(1) {
+                    let [r, g, b] = palette(below[i]);
+                    write!(out, "\x1b[48;2;{};{};{}m", r, g, b)?;
+                }
+                write!(out, "\u{2580}")?;
+            }
+            writeln!(out, "\x1b[0m")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{View, YAxis};
+    use crate::geom::Point;
+    use crate::point_set::PointSet;
+
+    fn ell() -> PointSet {
+        vec![Point { x: 0, y: 0 }, Point { x: 1, y: 0 }, Point { x: 0, y: 1 }, Point { x: 0, y: 2 }]
+            .into_iter().collect()
+    }
+
+    #[test]
+    fn text_orientation() {
+        let ps = ell();
+        let palette = |p| if ps.contains(p) { '#' } else { '.' };
+        assert_eq!(View::new(ps.bounding_box(), YAxis::Up).to_text(palette), "#.\n#.\n##\n");
+        assert_eq!(View::new(ps.bounding_box(), YAxis::Down).to_text(palette), "##\n#.\n#.\n");
+    }
+
+    #[test]
+    fn empty_view() {
+        let view = View::new((Point::origin(), Point { x: -1, y: -1 }), YAxis::Down);
+        assert_eq!((view.width(), view.height()), (0, 0));
+        assert_eq!(view.to_text(|_| '#'), "");
+    }
+
+    #[test]
+    fn pbm() {
+        let ps = ell();
+        let view = View::new((Point::origin(), Point { x: 8, y: 1 }), YAxis::Down);
+        let mut out = vec![];
+        view.write_pbm(&mut out, |p| ps.contains(p) || p.x == 8).unwrap();
+        assert_eq!(out, b"P4\n9 2\n\xc0\x80\x80\x80");
+    }
+
+    #[test]
+    fn pgm_and_ppm() {
+        let view = View::new((Point::origin(), Point { x: 1, y: 1 }), YAxis::Up);
+        let mut out = vec![];
+        view.write_pgm(&mut out, |p| (p.x * 10 + p.y) as u8).unwrap();
+        assert_eq!(out, b"P5\n2 2\n255\n\x01\x0b\x00\x0a");
+        out.clear();
+        view.write_ppm(&mut out, |p| [p.x as u8, p.y as u8, 7]).unwrap();
+        assert_eq!(out, b"P6\n2 2\n255\n\x00\x01\x07\x01\x01\x07\x00\x00\x07\x01\x00\x07");
+    }
+
+    #[test]
+    fn ansi_half_blocks() {
+        let view = View::new((Point::origin(), Point { x: 0, y: 2 }), YAxis::Down);
+        let mut out = vec![];
+        view.write_ansi(&mut out, |p| [p.y as u8; 3]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "\x1b[38;2;0;0;0m\x1b[48;2;1;1;1m\u{2580}\x1b[0m\n\x1b[38;2;2;2;2m\u{2580}\x1b[0m\n");
+    }
+}
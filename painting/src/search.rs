@@ -0,0 +1,268 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::ops::Add;
+
+use crate::geom::{Dir, Point};
+use crate::point_map::PointMap;
+use crate::point_set::PointSet;
+
+// The result of a search: the cost to reach each point that was
+// reached, and the point it was reached from.
+#[derive(Clone, Debug)]
+pub struct Paths<C> {
+    dist: PointMap<C>,
+    pred: PointMap<Point>,
+}
+
+impl<C: Copy> Paths<C> {
+    fn new(start: Point, zero: C) -> Self {
+        let mut dist = PointMap::new();
+        dist.insert(start, zero);
+        Self { dist, pred: PointMap::new() }
+    }
+
+    pub fn distance(&self, p: Point) -> Option<C> {
+        self.dist.get(p).cloned()
+    }
+
+    pub fn distances(&self) -> &PointMap<C> {
+        &self.dist
+    }
+
+    pub fn pred(&self, p: Point) -> Option<Point> {
+        self.pred.get(p).cloned()
+    }
+
+    // From the start to p, inclusive.
+    pub fn path_to(&self, p: Point) -> Option<Vec<Point>> {
+        if !self.dist.contains_key(p) {
+            return None;
+        }
+        let mut path = vec![p];
+        let mut here = p;
+        while let Some(&prev) = self.pred.get(here) {
+            path.push(prev);
+            here = prev;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    // None if p wasn't reached, or if some step isn't a unit move.
+    pub fn dirs_to(&self, p: Point) -> Option<Vec<Dir>> {
+        self.path_to(p)?.windows(2).map(|w| Dir::from_move(w[1] - w[0])).collect()
+    }
+}
+
+pub fn bfs<N, I>(start: Point, mut neighbors: N) -> Paths<usize>
+    where N: FnMut(Point) -> I,
+          I: IntoIterator<Item = Point>
+{
+    let mut paths = Paths::new(start, 0);
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    while let Some(here) = queue.pop_front() {
+        let d = paths.dist[here] + 1;
+        for next in neighbors(here) {
+            if !paths.dist.contains_key(next) {
+                paths.dist.insert(next, d);
+                paths.pred.insert(next, here);
+                queue.push_back(next);
+            }
+        }
+    }
+    paths
+}
+
+// A heap entry; the sequence number breaks ties first-in-first-out,
+// and means Point doesn't need to be Ord.
+struct Queued<C> {
+    key: C,
+    seq: usize,
+    p: Point,
+}
+
+impl<C: Ord> Ord for Queued<C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&other.key, other.seq).cmp(&(&self.key, self.seq))
+    }
+}
+
+impl<C: Ord> PartialOrd for Queued<C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<C: Ord> PartialEq for Queued<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<C: Ord> Eq for Queued<C> {}
+
+fn best_first<C, N, I, K, H>(start: Point, goal: Option<Point>, mut neighbors: N, mut cost: K,
+                             mut heuristic: H) -> Paths<C>
+    where C: Copy + Ord + Add<Output = C> + Default,
+          N: FnMut(Point) -> I,
+          I: IntoIterator<Item = Point>,
+          K: FnMut(Point, Point) -> C,
+          H: FnMut(Point) -> C
+{
+    let mut paths = Paths::new(start, C::default());
+    let mut done = PointSet::new();
+    let mut heap = BinaryHeap::new();
+    let mut seq = 0;
+    heap.push(Queued { key: heuristic(start), seq, p: start });
+    while let Some(Queued { p: here, .. }) = heap.pop() {
+        if !done.insert(here) {
+            continue;
+        }
+        if Some(here) == goal {
+            break;
+        }
+        let d = paths.dist[here];
+        for next in neighbors(here) {
+            let nd = d + cost(here, next);
+            if paths.dist.get(next).is_none_or(|&old| nd < old) {
+                paths.dist.insert(next, nd);
+                paths.pred.insert(next, here);
+                seq += 1;
+                heap.push(Queued { key: nd + heuristic(next), seq, p: next });
+            }
+        }
+    }
+    paths
+}
+
+// Costs must not be negative.
+pub fn dijkstra<C, N, I, K>(start: Point, neighbors: N, cost: K) -> Paths<C>
+    where C: Copy + Ord + Add<Output = C> + Default,
+          N: FnMut(Point) -> I,
+          I: IntoIterator<Item = Point>,
+          K: FnMut(Point, Point) -> C
+{
+    best_first(start, None, neighbors, cost, |_| C::default())
+}
+
+// Stops once the goal is reached, so only its path (and those of
+// points nearer than it) are guaranteed shortest.  The heuristic must
+// not overestimate the remaining cost.
+pub fn astar<C, N, I, K, H>(start: Point, goal: Point, neighbors: N, cost: K, heuristic: H) -> Paths<C>
+    where C: Copy + Ord + Add<Output = C> + Default,
+          N: FnMut(Point) -> I,
+          I: IntoIterator<Item = Point>,
+          K: FnMut(Point, Point) -> C,
+          H: FnMut(Point) -> C
+{
+    best_first(start, Some(goal), neighbors, cost, heuristic)
+}
+
+// How many steps it takes to spread from the start to each point that
+// is open and 4-connected to it.
+pub fn flood_fill<F>(start: Point, mut open: F) -> PointMap<usize>
+    where F: FnMut(Point) -> bool
+{
+    bfs(start, |p| p.neighbors4().filter(|&q| open(q)).collect::<Vec<_>>()).dist
+}
+
+// Labels the 4-connected components of a set, numbering from 0; also
+// returns how many there are.
+pub fn components(set: &PointSet) -> (PointMap<usize>, usize) {
+    let mut labels = PointMap::new();
+    let mut count = 0;
+    for p in set {
+        if labels.contains_key(p) {
+            continue;
+        }
+        for q in flood_fill(p, |q| set.contains(q)).keys() {
+            labels.insert(q, count);
+        }
+        count += 1;
+    }
+    (labels, count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::geom::Coord;
+    use crate::grid::Grid;
+
+    const MAZE: &[&str] = &[
+        "#########",
+        "#S..#...#",
+        "#.#.#.#.#",
+        "#.#...#G#",
+        "#########",
+    ];
+
+    fn maze() -> (Grid<char>, Point, Point) {
+        let grid = Grid::from_ascii(MAZE, |ch| ch).unwrap();
+        let find = |c| grid.iter().find(|&(_p, &ch)| ch == c).unwrap().0;
+        let (s, g) = (find('S'), find('G'));
+        (grid, s, g)
+    }
+
+    fn pt(x: Coord, y: Coord) -> Point {
+        Point { x, y }
+    }
+
+    #[test]
+    fn bfs_maze() {
+        let (grid, s, g) = maze();
+        let paths = bfs(s, |p| grid.neighbors4(p).filter(|&q| grid[q] != '#').collect::<Vec<_>>());
+        assert_eq!(paths.distance(g), Some(12));
+        assert_eq!(paths.distance(pt(1, 3)), Some(2));
+        assert_eq!(paths.distance(pt(0, 0)), None);
+        let path = paths.path_to(g).unwrap();
+        assert_eq!((path[0], path[12]), (s, g));
+        // The grid's y axis is downwards, so Up and Dn are swapped.
+        assert_eq!(paths.dirs_to(pt(3, 3)).unwrap(), vec![Dir::Rt, Dir::Rt, Dir::Up, Dir::Up]);
+    }
+
+    #[test]
+    fn dijkstra_weighted() {
+        // Moving right costs 1 and anything else costs 10.
+        let neighbors = |p: Point| p.neighbors4().filter(|q| q.x.abs() <= 3 && q.y.abs() <= 3);
+        let cost = |p: Point, q: Point| if q.x > p.x { 1 } else { 10 };
+        let paths = dijkstra(pt(0, 0), neighbors, cost);
+        assert_eq!(paths.distance(pt(3, 0)), Some(3));
+        assert_eq!(paths.distance(pt(-1, 0)), Some(10));
+        assert_eq!(paths.distance(pt(2, 2)), Some(22));
+        assert_eq!(paths.dirs_to(pt(2, 0)), Some(vec![Dir::Rt, Dir::Rt]));
+    }
+
+    #[test]
+    fn astar_matches_bfs() {
+        let (grid, s, g) = maze();
+        let neighbors = |p| grid.neighbors4(p).filter(|&q| grid[q] != '#').collect::<Vec<_>>();
+        let paths = astar(s, g, neighbors, |_, _| 1usize, |p| (g - p).len() as usize);
+        assert_eq!(paths.distance(g), Some(12));
+        assert_eq!(paths.path_to(g).unwrap().len(), 13);
+    }
+
+    #[test]
+    fn flood() {
+        let (grid, s, _g) = maze();
+        let fill = flood_fill(s, |p| grid.get(p).is_some_and(|&ch| ch != '#'));
+        assert_eq!(fill.len(), 15);
+        assert_eq!(fill.values().max(), Some(&12));
+    }
+
+    #[test]
+    fn labeled_components() {
+        let grid = Grid::from_ascii(&["##..#", "#...#", "..#..", "###.#"], |ch| ch == '#').unwrap();
+        let set: PointSet = grid.iter().filter(|&(_p, &b)| b).map(|(p, _b)| p).collect();
+        let (labels, count) = components(&set);
+        assert_eq!(count, 4);
+        assert_eq!(labels.len(), set.len());
+        assert_eq!(labels[pt(0, 0)], labels[pt(0, 1)]);
+        assert_eq!(labels[pt(4, 0)], labels[pt(4, 1)]);
+        assert_ne!(labels[pt(0, 0)], labels[pt(4, 0)]);
+        assert_eq!(labels[pt(2, 2)], labels[pt(0, 3)]);
+        assert_eq!(labels.get(pt(4, 2)), None);
+        assert_ne!(labels[pt(4, 1)], labels[pt(4, 3)]);
+    }
+}
@@ -0,0 +1,91 @@
+use crate::geom::{Dir, Len, Point};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Step {
+    Left,
+    Right,
+    Forward(Len),
+}
+
+// A position and heading, and the moves that got it there.
+#[derive(Clone, Debug)]
+pub struct Turtle {
+    pub pos: Point,
+    pub heading: Dir,
+    steps: Vec<Step>,
+}
+
+impl Turtle {
+    pub fn new(pos: Point, heading: Dir) -> Self {
+        Self { pos, heading, steps: vec![] }
+    }
+
+    // The point one step forward, without moving there.
+    pub fn ahead(&self) -> Point {
+        self.pos + self.heading.to_move()
+    }
+
+    pub fn forward(&mut self, n: Len) -> Point {
+        self.pos += self.heading.to_move() * n;
+        match self.steps.last_mut() {
+            _ if n == 0 => (),
+            Some(Step::Forward(m)) => *m += n,
+            _ => self.steps.push(Step::Forward(n)),
+        }
+        self.pos
+    }
+
+    pub fn left(&mut self) {
+        self.heading = self.heading.turn_left();
+        self.steps.push(Step::Left);
+    }
+
+    pub fn right(&mut self) {
+        self.heading = self.heading.turn_right();
+        self.steps.push(Step::Right);
+    }
+
+    // Recorded as two right turns.
+    pub fn reverse(&mut self) {
+        self.right();
+        self.right();
+    }
+
+    pub fn clear_path(&mut self) {
+        self.steps.clear();
+    }
+
+    // Like "L,12,R,8", with consecutive forward moves combined.
+    pub fn path(&self) -> String {
+        let words: Vec<String> = self.steps.iter().map(|step| match step {
+            Step::Left => "L".to_owned(),
+            Step::Right => "R".to_owned(),
+            Step::Forward(n) => n.to_string(),
+        }).collect();
+        words.join(",")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Turtle;
+    use crate::geom::{Dir, Point};
+
+    #[test]
+    fn moves_and_path() {
+        let mut t = Turtle::new(Point::origin(), Dir::Up);
+        t.left();
+        assert_eq!(t.forward(12), Point { x: -12, y: 0 });
+        t.right();
+        t.forward(3);
+        t.forward(0);
+        t.forward(5);
+        assert_eq!((t.pos, t.heading), (Point { x: -12, y: 8 }, Dir::Up));
+        assert_eq!(t.ahead(), Point { x: -12, y: 9 });
+        t.reverse();
+        assert_eq!(t.heading, Dir::Dn);
+        assert_eq!(t.path(), "L,12,R,8,R,R");
+        t.clear_path();
+        assert_eq!(t.path(), "");
+    }
+}